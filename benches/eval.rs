@@ -0,0 +1,51 @@
+//! Compares the time taken to evaluate formulas by walking their expression tree and by running
+//! their compiled bytecode. Run it with `cargo bench`, the same comparison runs in the browser
+//! with `wasm-pack test --headless --firefox --release`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use graph_fight::formula::{Function, Program};
+
+const FORMULAS: &[&str] = &[
+    "x",
+    "2 * x^2 - 3 * x + 1",
+    "sin(3 * x) * exp(-x^2 / 8) + max(0, x - 1)",
+    "atan2(sin(x), cos(x)) + ln(abs(x) + 1) - sqrt(x^2 + 1) % 0.5",
+];
+
+const POINTS: usize = 1_000_000;
+
+fn main() {
+    let xs: Vec<f64> = (0..POINTS)
+        .map(|i| i as f64 / POINTS as f64 * 20.0 - 10.0)
+        .collect();
+
+    println!(
+        "{:<70} {:>10} {:>10} {:>8}",
+        "formula", "tree", "bytecode", "speedup"
+    );
+
+    for src in FORMULAS {
+        let f = Function::parse(src, &["x"]).unwrap();
+        let p = Program::compile(&f);
+
+        let start = Instant::now();
+        let tree: Vec<f64> = xs.iter().map(|&x| f.eval(&[x])).collect();
+        black_box(tree);
+        let tree = start.elapsed().as_secs_f64();
+
+        let start = Instant::now();
+        black_box(p.eval_batch(black_box(&xs)));
+        let bytecode = start.elapsed().as_secs_f64();
+
+        let per_eval = |secs: f64| format!("{:.1} ns", secs * 1e9 / POINTS as f64);
+        println!(
+            "{:<70} {:>10} {:>10} {:>7.1}x",
+            src,
+            per_eval(tree),
+            per_eval(bytecode),
+            tree / bytecode
+        );
+    }
+}
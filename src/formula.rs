@@ -0,0 +1,349 @@
+mod angle;
+mod ast;
+mod builtin;
+mod bytecode;
+mod complexity;
+mod derivative;
+mod diagnostic;
+mod error;
+mod function;
+mod helpers;
+mod highlight;
+mod latex;
+mod lexer;
+mod limits;
+mod parser;
+mod render;
+mod simplify;
+
+pub use self::angle::{to_radians, AngleUnit};
+pub use self::ast::{BinaryOp, Expr, ExprKind, UnaryOp};
+pub use self::builtin::{constant, Arity, Func};
+pub use self::bytecode::Program;
+pub use self::complexity::complexity;
+pub use self::derivative::derivative;
+pub use self::diagnostic::{Diagnostic, Diagnostics, Severity};
+pub use self::error::{Error, ErrorKind};
+pub use self::function::{check, Function};
+pub use self::helpers::{Definition, Helpers};
+pub use self::highlight::{highlight, tokenize_formula, SyntaxToken, SyntaxTokens, TokenCategory};
+pub use self::latex::{is_latex, parse_latex};
+pub use self::lexer::{tokenize, Span, Token, TokenKind};
+pub use self::limits::{Feature, Limits};
+pub use self::parser::{parse, parse_definition, parse_list, Name};
+pub use self::render::{to_latex, to_mathml, Notation};
+pub use self::simplify::{simplify, simplify_in};
+
+/// Canonical text of the comma separated formulas in `src`, with the operators the player left
+/// out written down, Unicode notation and LaTeX spelled out and only the necessary parentheses. Two
+/// formulas that parse the same have the same canonical text, which reads back as the same
+/// formulas.
+pub fn normalize(src: &str) -> Result<String, Error> {
+    render(src, Notation::Text)
+}
+
+/// The comma separated formulas in `src` written out in `notation` with only the necessary
+/// parentheses, to display them. The calls to helpers are kept as written.
+pub fn render(src: &str, notation: Notation) -> Result<String, Error> {
+    let exprs = parse_source(src)?;
+
+    Ok(match notation {
+        Notation::Text => {
+            let texts: Vec<String> = exprs.iter().map(|e| e.to_string()).collect();
+            texts.join(", ")
+        }
+        Notation::Latex => {
+            let texts: Vec<String> = exprs.iter().map(to_latex).collect();
+            texts.join(", ")
+        }
+        Notation::MathMl => {
+            let markups: Vec<String> = exprs.iter().map(to_mathml).collect();
+            format!(
+                r#"<math xmlns="http://www.w3.org/1998/Math/MathML">{}</math>"#,
+                markups.join("<mo>,</mo>")
+            )
+        }
+    })
+}
+
+/// Parses `src` as a list of comma separated formulas, in LaTeX if it looks like LaTeX
+fn parse_source(src: &str) -> Result<Vec<Expr>, Error> {
+    if is_latex(src) {
+        parse_latex(src)
+    } else {
+        parse_list(src)
+    }
+}
+
+/// What a formula is read against: the variables it is bound to, the number of comma separated
+/// formulas it is made of, the helpers it may call, the limits it must fit within and the unit of
+/// its angles
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    pub vars: &'a [&'a str],
+    pub components: usize,
+    pub helpers: &'a Helpers,
+    pub limits: &'a Limits,
+    pub unit: AngleUnit,
+}
+
+/// Parses `src` as the comma separated formulas of `ctx` and expands the calls to its helpers,
+/// failing on the first problem that prevents reading it as a whole
+fn read(src: &str, ctx: &Context) -> Result<Vec<Expr>, Error> {
+    ctx.limits.check_source(src)?;
+    let exprs = parse_source(src)?;
+
+    if exprs.len() != ctx.components {
+        return Err(Error::new(
+            Span::new(0, src.len()),
+            ErrorKind::WrongComponentCount {
+                expected: ctx.components,
+                found: exprs.len(),
+            },
+        ));
+    }
+
+    exprs
+        .into_iter()
+        .map(|e| ctx.helpers.expand(e, ctx.limits))
+        .collect()
+}
+
+/// Parses `src` as the comma separated formulas of `ctx`, each bound to its variables
+pub fn parse_functions(src: &str, ctx: &Context) -> Result<Vec<Function>, Error> {
+    let exprs = read(src, ctx)?;
+
+    if let Some(e) = ctx.limits.check(&exprs).into_iter().next() {
+        return Err(e);
+    }
+
+    exprs
+        .into_iter()
+        .map(|e| Function::new(to_radians(e, ctx.unit), ctx.vars))
+        .collect()
+}
+
+/// Complexity score of the comma separated formulas of `ctx` in `src`, once the calls to its
+/// helpers are expanded. See [`complexity`].
+pub fn measure(src: &str, ctx: &Context) -> Result<usize, Error> {
+    let exprs = read(src, ctx)?;
+    Ok(complexity(&exprs))
+}
+
+/// Checks `formula`, made of the comma separated formulas of `ctx`, without shooting it. The
+/// variables of `ctx` take the values `at` at the start of the shot, to catch formulas that cannot
+/// even be evaluated there.
+pub fn validate(formula: &str, at: &[f64], ctx: &Context) -> Diagnostics {
+    let (vars, limits) = (ctx.vars, ctx.limits);
+    let exprs = match read(formula, ctx) {
+        Ok(exprs) => exprs,
+        Err(e) => return Diagnostics(vec![e.into()]),
+    };
+
+    let mut diagnostics: Vec<Diagnostic> = limits
+        .check(&exprs)
+        .into_iter()
+        .chain(exprs.iter().flat_map(|e| check(e, vars)))
+        .map(Diagnostic::from)
+        .collect();
+
+    if !exprs.iter().any(|e| vars.iter().any(|v| e.uses_var(v))) {
+        let names: Vec<String> = vars.iter().map(|v| format!("`{}`", v)).collect();
+        diagnostics.push(Diagnostic::warning(
+            0,
+            formula.len(),
+            format!("The formula does not use {}", names.join(" or ")),
+        ));
+    }
+
+    if diagnostics.iter().all(|d| !d.is_error()) {
+        let fs: Vec<Function> = exprs
+            .into_iter()
+            .map(|e| Function::new(to_radians(e, ctx.unit), vars).unwrap())
+            .collect();
+
+        if !fs.iter().all(|f| f.eval(at).is_finite()) {
+            let values: Vec<String> = vars
+                .iter()
+                .zip(at)
+                .map(|(v, a)| format!("{} = {}", v, a))
+                .collect();
+            diagnostics.push(Diagnostic::error(
+                0,
+                formula.len(),
+                format!(
+                    "The formula is not a finite number at {}",
+                    values.join(", ")
+                ),
+            ));
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.start);
+    Diagnostics(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Context of a formula of `x`
+    fn context<'a>(helpers: &'a Helpers, limits: &'a Limits) -> Context<'a> {
+        Context {
+            vars: &["x"],
+            components: 1,
+            helpers,
+            limits,
+            unit: AngleUnit::Radians,
+        }
+    }
+
+    fn spans_in(formula: &str, ctx: &Context) -> Vec<(usize, usize, Severity)> {
+        let at = vec![1.0; ctx.vars.len()];
+        validate(formula, &at, ctx)
+            .0
+            .into_iter()
+            .map(|d| (d.start, d.end, d.severity))
+            .collect()
+    }
+
+    fn spans(formula: &str) -> Vec<(usize, usize, Severity)> {
+        spans_in(formula, &context(&Helpers::new(), &Limits::default()))
+    }
+
+    #[test]
+    fn test_valid() {
+        assert!(spans("sin(x) + 2 * x^2").is_empty());
+    }
+
+    #[test]
+    fn test_unknown_identifier() {
+        assert_eq!(spans("x + foo"), vec![(4, 7, Severity::Error)]);
+    }
+
+    #[test]
+    fn test_unbalanced_parens() {
+        assert_eq!(spans("(x + 1))"), vec![(7, 8, Severity::Error)]);
+        assert_eq!(spans("sin((x)"), vec![(3, 4, Severity::Error)]);
+    }
+
+    #[test]
+    fn test_missing_variable() {
+        assert_eq!(spans("3"), vec![(0, 1, Severity::Warning)]);
+    }
+
+    #[test]
+    fn test_components() {
+        let (helpers, limits) = (Helpers::new(), Limits::default());
+        let ctx = Context {
+            vars: &["t"],
+            components: 2,
+            ..context(&helpers, &limits)
+        };
+
+        assert_eq!(
+            spans_in("cos(t), ln(t - 1)", &ctx),
+            vec![(0, 17, Severity::Error)]
+        );
+        assert!(spans_in("cos(t), sin(t)", &ctx).is_empty());
+        assert_eq!(spans_in("cos(t)", &ctx), vec![(0, 6, Severity::Error)]);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut limits = Limits::default();
+        limits.set_max_length(12);
+        limits.set_functions(vec!["sin".into()]).unwrap();
+        let helpers = Helpers::new();
+        let ctx = context(&helpers, &limits);
+
+        assert_eq!(
+            spans_in("sin(x) + cos(x)", &ctx),
+            vec![(12, 15, Severity::Error)]
+        );
+        assert_eq!(spans_in("x + cos(x)", &ctx), vec![(4, 10, Severity::Error)]);
+
+        let e = parse_functions("x + cos(x)", &ctx).unwrap_err();
+        assert_eq!(e.kind, ErrorKind::ForbiddenFunction("cos".into()));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("2x² + 3sin(πx)").unwrap(),
+            "2 * x^2 + 3 * sin(pi * x)"
+        );
+        assert_eq!(
+            normalize("(t+1)(t-2) ,|t|").unwrap(),
+            "(t + 1) * (t - 2), abs(t)"
+        );
+        assert_eq!(normalize("((x))").unwrap(), "x");
+        assert!(normalize("2x +").is_err());
+
+        assert_eq!(normalize(r"\frac{t}{2}, \sin t").unwrap(), "t / 2, sin(t)");
+
+        let canonical = normalize("x⁻²|x - 1|").unwrap();
+        assert_eq!(normalize(&canonical).unwrap(), canonical);
+
+        assert_eq!(
+            normalize("1e300 x + 2.5e-12").unwrap(),
+            "1e300 * x + 2.5e-12"
+        );
+        assert_eq!(
+            normalize("0.00125 + 1e14").unwrap(),
+            "0.00125 + 100000000000000"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("cos(t), sin(t)/2", Notation::Latex).unwrap(),
+            r"\cos(t), \frac{\sin(t)}{2}"
+        );
+        assert_eq!(
+            render("t, 1", Notation::MathMl).unwrap(),
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mi>t</mi><mo>,</mo><mn>1</mn>\
+             </math>"
+        );
+        assert_eq!(render(r"\frac{x}{2}", Notation::Text).unwrap(), "x / 2");
+        assert!(render("2x +", Notation::Latex).is_err());
+    }
+
+    #[test]
+    fn test_helpers() {
+        let limits = Limits::default();
+        let mut helpers = Helpers::new();
+        helpers.define("g(x) = ln(x)", &limits).unwrap();
+        let ctx = context(&helpers, &limits);
+
+        assert_eq!(
+            spans_in("g(x) + g(x, 2)", &ctx),
+            vec![(7, 14, Severity::Error)]
+        );
+        assert_eq!(
+            spans_in("2 * g(x - 1)", &ctx),
+            vec![(0, 12, Severity::Error)]
+        );
+
+        let fs = parse_functions("g(x)", &ctx).unwrap();
+        assert_eq!(fs[0].expr().to_string(), "ln(x)");
+    }
+
+    #[test]
+    fn test_latex() {
+        let (helpers, limits) = (Helpers::new(), Limits::default());
+        let ctx = context(&helpers, &limits);
+
+        assert!(spans(r"\frac{1}{2}\sin\left(x\right)+\sqrt{x}").is_empty());
+
+        let d = validate(r"x + \log x", &[1.0], &ctx);
+        assert_eq!((d.0[0].start, d.0[0].end), (4, 8));
+        assert_eq!(d.0[0].message, "The LaTeX command `\\log` is not supported");
+    }
+
+    #[test]
+    fn test_non_finite() {
+        assert_eq!(spans("ln(x - 1)"), vec![(0, 9, Severity::Error)]);
+    }
+}
@@ -0,0 +1,159 @@
+use wasm_bindgen::prelude::*;
+
+use super::{BinaryOp, Expr, ExprKind, Func};
+
+/// Unit of the angles taken by the trigonometric functions and given by their inverses
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AngleUnit {
+    Radians,
+    Degrees,
+}
+
+impl AngleUnit {
+    /// Size of one unit in radians
+    pub fn radians(&self) -> f64 {
+        match self {
+            AngleUnit::Radians => 1.0,
+            AngleUnit::Degrees => std::f64::consts::PI / 180.0,
+        }
+    }
+
+    /// Size of one unit in radians as a formula, like `pi / 180`, `None` for radians
+    pub fn factor(&self) -> Option<Expr> {
+        match self {
+            AngleUnit::Radians => None,
+            AngleUnit::Degrees => Some(Expr::binary(
+                BinaryOp::Div,
+                Expr::var("pi"),
+                Expr::number(180.0),
+            )),
+        }
+    }
+
+    /// Applies `func` to `args` with angles in this unit
+    pub fn apply(&self, func: Func, args: &[f64]) -> f64 {
+        if func.takes_angle() {
+            func.apply(&[args[0] * self.radians()])
+        } else if func.gives_angle() {
+            func.apply(args) / self.radians()
+        } else {
+            func.apply(args)
+        }
+    }
+}
+
+/// `expr`, whose angles are in `unit`, with its angles in radians like the evaluator expects.
+/// The arguments of the trigonometric functions are converted to radians and the results of
+/// their inverses are converted back to `unit`. The new nodes take the span of the call.
+pub fn to_radians(expr: Expr, unit: AngleUnit) -> Expr {
+    let factor = match unit.factor() {
+        Some(factor) => factor,
+        None => return expr,
+    };
+    convert(expr, &factor)
+}
+
+fn convert(expr: Expr, factor: &Expr) -> Expr {
+    let span = expr.span;
+    let at_span = |mut e: Expr| {
+        e.span = span;
+        e
+    };
+
+    let kind = match expr.kind {
+        kind @ (ExprKind::Number(_) | ExprKind::Var(_)) => kind,
+        ExprKind::Unary(op, e) => ExprKind::Unary(op, Box::new(convert(*e, factor))),
+        ExprKind::Binary(op, l, r) => ExprKind::Binary(
+            op,
+            Box::new(convert(*l, factor)),
+            Box::new(convert(*r, factor)),
+        ),
+        ExprKind::Call(name, args) => {
+            let mut args: Vec<Expr> = args.into_iter().map(|a| convert(a, factor)).collect();
+
+            match Func::from_name(&name) {
+                Some(f) if f.takes_angle() && !args.is_empty() => {
+                    let angle = args.remove(0);
+                    let radians = Expr::binary(BinaryOp::Mul, angle, factor.clone());
+                    args.insert(0, at_span(radians));
+                    ExprKind::Call(name, args)
+                }
+                Some(f) if f.gives_angle() => {
+                    let call = Expr::new(ExprKind::Call(name, args), span);
+                    return at_span(Expr::binary(BinaryOp::Div, call, factor.clone()));
+                }
+                _ => ExprKind::Call(name, args),
+            }
+        }
+    };
+
+    Expr::new(kind, span)
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::*;
+    use crate::formula::{parse, Function};
+
+    fn eval(src: &str, unit: AngleUnit, x: f64) -> f64 {
+        let expr = to_radians(parse(src).unwrap(), unit);
+        Function::new(expr, &["x"]).unwrap().eval(&[x])
+    }
+
+    #[test]
+    fn test_to_radians() {
+        assert_eq!(
+            to_radians(parse("sin(x) + atan2(1, x)").unwrap(), AngleUnit::Degrees).to_string(),
+            "sin(x * (pi / 180)) + atan2(1, x) / (pi / 180)"
+        );
+        assert_eq!(
+            to_radians(parse("sin(x)").unwrap(), AngleUnit::Radians),
+            parse("sin(x)").unwrap()
+        );
+
+        assert!(approx_eq!(
+            f64,
+            eval("sin(x)", AngleUnit::Degrees, 30.0),
+            0.5,
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(
+            f64,
+            eval("acos(x)", AngleUnit::Degrees, 0.0),
+            90.0,
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(
+            f64,
+            eval("tan(atan(x))", AngleUnit::Degrees, 2.0),
+            2.0,
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(
+            f64,
+            eval("sinh(x)", AngleUnit::Degrees, 1.0),
+            1f64.sinh()
+        ));
+    }
+
+    #[test]
+    fn test_apply() {
+        let unit = AngleUnit::Degrees;
+        assert!(approx_eq!(
+            f64,
+            unit.apply(Func::Cos, &[60.0]),
+            0.5,
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(
+            f64,
+            unit.apply(Func::Atan2, &[1.0, 1.0]),
+            45.0,
+            epsilon = 1e-12
+        ));
+        assert_eq!(unit.apply(Func::Abs, &[-2.0]), 2.0);
+    }
+}
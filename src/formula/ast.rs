@@ -0,0 +1,289 @@
+use std::fmt;
+
+use super::{Func, Span};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    /// Comparisons are 1 when they hold and 0 otherwise
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+/// Binding strength of each kind of expression, used to decide where parentheses are needed
+const PREC_CMP: u8 = 0;
+const PREC_ADD: u8 = 1;
+pub(super) const PREC_MUL: u8 = 2;
+pub(super) const PREC_NEG: u8 = 3;
+pub(super) const PREC_POW: u8 = 4;
+pub(super) const PREC_ATOM: u8 = 5;
+
+impl BinaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Pow => "^",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+        }
+    }
+
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Add | BinaryOp::Sub => PREC_ADD,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => PREC_MUL,
+            BinaryOp::Pow => PREC_POW,
+            _ => PREC_CMP,
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        self.precedence() == PREC_CMP
+    }
+
+    /// True if `a op b op c` means `a op (b op c)`
+    pub fn is_right_assoc(&self) -> bool {
+        *self == BinaryOp::Pow
+    }
+
+    /// Precedence below which the left and right operands need parentheses
+    pub(super) fn operand_precedence(&self) -> (u8, u8) {
+        let prec = self.precedence();
+        // Comparisons cannot be chained
+        if self.is_comparison() {
+            (prec + 1, prec + 1)
+        } else if self.is_right_assoc() {
+            (prec + 1, prec)
+        } else {
+            (prec, prec + 1)
+        }
+    }
+
+    pub fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Rem => a % b,
+            BinaryOp::Pow => a.powf(b),
+            BinaryOp::Lt => compare(a, b, a < b),
+            BinaryOp::Le => compare(a, b, a <= b),
+            BinaryOp::Gt => compare(a, b, a > b),
+            BinaryOp::Ge => compare(a, b, a >= b),
+            BinaryOp::Eq => compare(a, b, a == b),
+            BinaryOp::Ne => compare(a, b, a != b),
+        }
+    }
+}
+
+/// Value of a comparison between `a` and `b`, NaN if either of them is NaN
+fn compare(a: f64, b: f64, holds: bool) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if holds {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+    Number(f64),
+    /// A variable or a named constant
+    Var(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// A node of a parsed formula. Two expressions are equal if they have the same structure, no
+/// matter which part of the source they come from.
+#[derive(Clone, Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Expr) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Expr {
+        Expr { kind, span }
+    }
+
+    pub fn number(value: f64) -> Expr {
+        Expr::new(ExprKind::Number(value), Span::default())
+    }
+
+    pub fn var(name: &str) -> Expr {
+        Expr::new(ExprKind::Var(name.into()), Span::default())
+    }
+
+    pub fn unary(op: UnaryOp, expr: Expr) -> Expr {
+        let span = expr.span;
+        Expr::new(ExprKind::Unary(op, Box::new(expr)), span)
+    }
+
+    pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        let span = lhs.span.join(rhs.span);
+        Expr::new(ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), span)
+    }
+
+    pub fn call(name: &str, args: Vec<Expr>) -> Expr {
+        let span = args
+            .iter()
+            .map(|a| a.span)
+            .reduce(|a, b| a.join(b))
+            .unwrap_or_default();
+        Expr::new(ExprKind::Call(name.into(), args), span)
+    }
+
+    /// Direct sub-expressions of this node
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Number(_) | ExprKind::Var(_) => Vec::new(),
+            ExprKind::Unary(_, e) => vec![e],
+            ExprKind::Binary(_, l, r) => vec![l, r],
+            ExprKind::Call(_, args) => args.iter().collect(),
+        }
+    }
+
+    /// Calls `f` on this node and all its descendants, parents before children
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        for child in self.children() {
+            child.walk(f);
+        }
+    }
+
+    /// Number of nodes of the expression
+    pub fn size(&self) -> usize {
+        let mut size = 0;
+        self.walk(&mut |_| size += 1);
+        size
+    }
+
+    /// True if the variable `name` appears in the expression
+    pub fn uses_var(&self, name: &str) -> bool {
+        let mut found = false;
+        self.walk(&mut |e| {
+            if matches!(&e.kind, ExprKind::Var(v) if v == name) {
+                found = true;
+            }
+        });
+        found
+    }
+
+    pub(super) fn precedence(&self) -> u8 {
+        match &self.kind {
+            ExprKind::Number(v) if v.is_sign_negative() => PREC_NEG,
+            ExprKind::Number(_) | ExprKind::Var(_) | ExprKind::Call(..) => PREC_ATOM,
+            ExprKind::Unary(UnaryOp::Neg, _) => PREC_NEG,
+            ExprKind::Binary(op, _, _) => op.precedence(),
+        }
+    }
+}
+
+/// Mantissa and exponent of `v` if it is too large or too small to be written in full, like
+/// `("2.5", -12)` for `2.5e-12`. The mantissa is the shortest one reading back as `v`.
+pub(super) fn exponent_form(v: f64) -> Option<(String, i32)> {
+    if v == 0.0 || (1e-5..1e15).contains(&v.abs()) {
+        return None;
+    }
+
+    let text = format!("{:e}", v);
+    let (mantissa, exponent) = text.split_once('e')?;
+    Some((mantissa.into(), exponent.parse().ok()?))
+}
+
+/// Writes `v` so that it reads back as the same number, switching to exponent notation, like
+/// `1e300`, when it is too large or too small to be written in full
+fn fmt_number(f: &mut fmt::Formatter<'_>, v: f64) -> fmt::Result {
+    match exponent_form(v) {
+        Some((mantissa, exponent)) => write!(f, "{}e{}", mantissa, exponent),
+        None => write!(f, "{}", v),
+    }
+}
+
+/// Writes `expr`, between parentheses if it binds weaker than `min_prec`
+fn fmt_operand(f: &mut fmt::Formatter<'_>, expr: &Expr, min_prec: u8) -> fmt::Result {
+    if expr.precedence() < min_prec {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Number(v) => fmt_number(f, *v),
+            ExprKind::Var(name) => write!(f, "{}", name),
+            ExprKind::Unary(UnaryOp::Neg, e) => {
+                write!(f, "-")?;
+                fmt_operand(f, e, PREC_NEG)
+            }
+            ExprKind::Binary(op, l, r) => {
+                let (l_prec, r_prec) = op.operand_precedence();
+                fmt_operand(f, l, l_prec)?;
+                if *op == BinaryOp::Pow {
+                    write!(f, "^")?;
+                } else {
+                    write!(f, " {} ", op.symbol())?;
+                }
+                fmt_operand(f, r, r_prec)
+            }
+            ExprKind::Call(name, args) if name == Func::Piecewise.name() && args.len() > 1 => {
+                write!(f, "{{")?;
+                for (i, branch) in args.chunks(2).enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    match branch {
+                        [cond, value] => write!(f, "{}: {}", cond, value)?,
+                        _ => write!(f, "{}", branch[0])?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            ExprKind::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::f64::consts;
+
+/// Functions that can be called from a formula
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Func {
+    Sqrt,
+    Exp,
+    Ln,
+    Abs,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Floor,
+    Ceil,
+    Round,
+    Signum,
+    Atan2,
+    Max,
+    Min,
+    /// `if(cond, a, b)` is `a` where `cond` is true and `b` elsewhere
+    If,
+    /// 1 from 0 on and 0 before
+    Step,
+    /// `clamp(x, lo, hi)` keeps `x` between `lo` and `hi`
+    Clamp,
+    /// Pairs of a condition and a value, with an optional value for when no condition holds.
+    /// Written `{cond: value; cond: value; value}` in formulas.
+    Piecewise,
+}
+
+/// Number of arguments accepted by a function
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, n: usize) -> bool {
+        match *self {
+            Arity::Exactly(m) => n == m,
+            Arity::AtLeast(m) => n >= m,
+        }
+    }
+}
+
+impl std::fmt::Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exactly(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+        }
+    }
+}
+
+impl Func {
+    pub const ALL: &'static [Func] = &[
+        Func::Sqrt,
+        Func::Exp,
+        Func::Ln,
+        Func::Abs,
+        Func::Sin,
+        Func::Cos,
+        Func::Tan,
+        Func::Asin,
+        Func::Acos,
+        Func::Atan,
+        Func::Sinh,
+        Func::Cosh,
+        Func::Tanh,
+        Func::Asinh,
+        Func::Acosh,
+        Func::Atanh,
+        Func::Floor,
+        Func::Ceil,
+        Func::Round,
+        Func::Signum,
+        Func::Atan2,
+        Func::Max,
+        Func::Min,
+        Func::If,
+        Func::Step,
+        Func::Clamp,
+        Func::Piecewise,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Func> {
+        Func::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Func::Sqrt => "sqrt",
+            Func::Exp => "exp",
+            Func::Ln => "ln",
+            Func::Abs => "abs",
+            Func::Sin => "sin",
+            Func::Cos => "cos",
+            Func::Tan => "tan",
+            Func::Asin => "asin",
+            Func::Acos => "acos",
+            Func::Atan => "atan",
+            Func::Sinh => "sinh",
+            Func::Cosh => "cosh",
+            Func::Tanh => "tanh",
+            Func::Asinh => "asinh",
+            Func::Acosh => "acosh",
+            Func::Atanh => "atanh",
+            Func::Floor => "floor",
+            Func::Ceil => "ceil",
+            Func::Round => "round",
+            Func::Signum => "signum",
+            Func::Atan2 => "atan2",
+            Func::Max => "max",
+            Func::Min => "min",
+            Func::If => "if",
+            Func::Step => "step",
+            Func::Clamp => "clamp",
+            Func::Piecewise => "piecewise",
+        }
+    }
+
+    /// True for the trigonometric functions, whose argument is an angle
+    pub fn takes_angle(&self) -> bool {
+        matches!(self, Func::Sin | Func::Cos | Func::Tan)
+    }
+
+    /// True for the inverse trigonometric functions, whose result is an angle
+    pub fn gives_angle(&self) -> bool {
+        matches!(self, Func::Asin | Func::Acos | Func::Atan | Func::Atan2)
+    }
+
+    pub fn arity(&self) -> Arity {
+        match self {
+            Func::Atan2 => Arity::Exactly(2),
+            Func::Max | Func::Min => Arity::AtLeast(1),
+            Func::If | Func::Clamp => Arity::Exactly(3),
+            Func::Piecewise => Arity::AtLeast(2),
+            _ => Arity::Exactly(1),
+        }
+    }
+
+    /// Applies the function, `args` must have a length accepted by [`Func::arity`]
+    pub fn apply(&self, args: &[f64]) -> f64 {
+        let x = args[0];
+
+        match self {
+            Func::Sqrt => x.sqrt(),
+            Func::Exp => x.exp(),
+            Func::Ln => x.ln(),
+            Func::Abs => x.abs(),
+            Func::Sin => x.sin(),
+            Func::Cos => x.cos(),
+            Func::Tan => x.tan(),
+            Func::Asin => x.asin(),
+            Func::Acos => x.acos(),
+            Func::Atan => x.atan(),
+            Func::Sinh => x.sinh(),
+            Func::Cosh => x.cosh(),
+            Func::Tanh => x.tanh(),
+            Func::Asinh => x.asinh(),
+            Func::Acosh => x.acosh(),
+            Func::Atanh => x.atanh(),
+            Func::Floor => x.floor(),
+            Func::Ceil => x.ceil(),
+            Func::Round => x.round(),
+            Func::Signum => x.signum(),
+            Func::Atan2 => x.atan2(args[1]),
+            // f64::max and f64::min skip NaN, which would hide an undefined argument
+            Func::Max | Func::Min | Func::Clamp if args.iter().any(|v| v.is_nan()) => f64::NAN,
+            Func::Max => args.iter().fold(f64::NEG_INFINITY, |m, &v| m.max(v)),
+            Func::Min => args.iter().fold(f64::INFINITY, |m, &v| m.min(v)),
+            Func::If => piecewise(args),
+            Func::Step => {
+                if x.is_nan() {
+                    f64::NAN
+                } else if x >= 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Func::Clamp => x.max(args[1]).min(args[2]),
+            Func::Piecewise => piecewise(args),
+        }
+    }
+}
+
+/// Value of the first branch of `args` whose condition holds, each branch being a condition
+/// followed by its value. A last value without condition applies when no condition holds, and
+/// without it the result is NaN. Conditions hold when they are not 0, and a NaN condition makes
+/// the result NaN since no branch can be picked.
+fn piecewise(args: &[f64]) -> f64 {
+    let mut branches = args.chunks(2);
+
+    for branch in &mut branches {
+        match *branch {
+            [cond, _] if cond.is_nan() => return f64::NAN,
+            [cond, value] if cond != 0.0 => return value,
+            [_, _] => {}
+            [default] => return default,
+            _ => unreachable!(),
+        }
+    }
+
+    f64::NAN
+}
+
+/// Value of the named constant, if it exists
+pub fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" => Some(consts::PI),
+        "e" => Some(consts::E),
+        _ => None,
+    }
+}
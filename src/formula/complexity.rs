@@ -0,0 +1,94 @@
+use super::{Expr, ExprKind, Func};
+
+/// Complexity score of the components of a formula, to keep formulas short and readable. Every
+/// node of the expression tree counts 1, each call to a function adds the weight of the function
+/// and each number adds its significant digits after the first one, so that `3.14159` costs as
+/// much as 6 nodes.
+pub fn complexity(exprs: &[Expr]) -> usize {
+    let mut score = 0;
+
+    for expr in exprs {
+        expr.walk(&mut |e| {
+            score += 1 + match &e.kind {
+                ExprKind::Number(v) => digits(*v) - 1,
+                ExprKind::Call(name, _) => Func::from_name(name).map_or(0, weight),
+                _ => 0,
+            };
+        });
+    }
+
+    score
+}
+
+/// Extra cost of calling `func`, higher for the functions that bend the curve in less obvious
+/// ways
+fn weight(func: Func) -> usize {
+    match func {
+        Func::Sqrt
+        | Func::Abs
+        | Func::Floor
+        | Func::Ceil
+        | Func::Round
+        | Func::Signum
+        | Func::Step
+        | Func::Max
+        | Func::Min => 1,
+        Func::Exp
+        | Func::Ln
+        | Func::Sin
+        | Func::Cos
+        | Func::Tan
+        | Func::If
+        | Func::Clamp
+        | Func::Piecewise => 2,
+        Func::Asin
+        | Func::Acos
+        | Func::Atan
+        | Func::Atan2
+        | Func::Sinh
+        | Func::Cosh
+        | Func::Tanh => 3,
+        Func::Asinh | Func::Acosh | Func::Atanh => 4,
+    }
+}
+
+/// Number of significant digits of `v` in its shortest decimal form, 1 for `0` or `2000`
+fn digits(v: f64) -> usize {
+    let text = format!("{:e}", v.abs());
+    let mantissa = text.split('e').next().unwrap_or_default();
+    mantissa
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .count()
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::parse_list;
+
+    fn score(src: &str) -> usize {
+        complexity(&parse_list(src).unwrap())
+    }
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(digits(0.0), 1);
+        assert_eq!(digits(2000.0), 1);
+        assert_eq!(digits(0.25), 2);
+        assert_eq!(digits(-4.12345), 6);
+        assert_eq!(digits(1e-7), 1);
+    }
+
+    #[test]
+    fn test_complexity() {
+        assert_eq!(score("x"), 1);
+        assert_eq!(score("2 * x + 1"), 5);
+        assert_eq!(score("sin(x)"), 4);
+        assert_eq!(score("atanh(x) + abs(x)"), 10);
+        assert_eq!(score("3.14159 * x"), 8);
+        assert_eq!(score("cos(t), sin(t)"), 8);
+        assert!(score("0.0123 * x^3 - 1.0457 * x^2 + 0.3312 * x") > 2 * score("x^3 - x^2 + x"));
+    }
+}
@@ -0,0 +1,282 @@
+use super::builtin::Func;
+use super::{simplify_in, AngleUnit, BinaryOp, Expr, ExprKind, UnaryOp};
+
+/// Derivative of `expr` with respect to the variable `var`, simplified. Every other identifier is
+/// taken as a constant. Functions that are constant by pieces, like `floor` or comparisons, have
+/// a derivative of 0, the derivatives of `max` and `min` use `signum` to pick the active argument
+/// and conditional expressions keep their conditions. The angles of `expr` are in `unit`, and so
+/// are the ones of its derivative.
+pub fn derivative(expr: &Expr, var: &str, unit: AngleUnit) -> Expr {
+    simplify_in(diff(expr, var, unit), unit)
+}
+
+fn num(v: f64) -> Expr {
+    Expr::number(v)
+}
+
+fn add(l: Expr, r: Expr) -> Expr {
+    Expr::binary(BinaryOp::Add, l, r)
+}
+
+fn sub(l: Expr, r: Expr) -> Expr {
+    Expr::binary(BinaryOp::Sub, l, r)
+}
+
+fn mul(l: Expr, r: Expr) -> Expr {
+    Expr::binary(BinaryOp::Mul, l, r)
+}
+
+fn div(l: Expr, r: Expr) -> Expr {
+    Expr::binary(BinaryOp::Div, l, r)
+}
+
+fn pow(l: Expr, r: Expr) -> Expr {
+    Expr::binary(BinaryOp::Pow, l, r)
+}
+
+fn neg(e: Expr) -> Expr {
+    Expr::unary(UnaryOp::Neg, e)
+}
+
+fn call(func: Func, args: Vec<Expr>) -> Expr {
+    Expr::call(func.name(), args)
+}
+
+fn diff(expr: &Expr, var: &str, unit: AngleUnit) -> Expr {
+    match &expr.kind {
+        ExprKind::Number(_) => num(0.0),
+        ExprKind::Var(name) => num(if name == var { 1.0 } else { 0.0 }),
+        ExprKind::Unary(UnaryOp::Neg, e) => neg(diff(e, var, unit)),
+        ExprKind::Binary(op, l, r) => diff_binary(*op, l, r, var, unit),
+        ExprKind::Call(name, args) => match Func::from_name(name) {
+            Some(f) => diff_call(f, args, var, unit),
+            None => num(f64::NAN),
+        },
+    }
+}
+
+fn diff_binary(op: BinaryOp, l: &Expr, r: &Expr, var: &str, unit: AngleUnit) -> Expr {
+    let (u, v) = (l.clone(), r.clone());
+    let (du, dv) = (diff(l, var, unit), diff(r, var, unit));
+
+    match op {
+        BinaryOp::Add => add(du, dv),
+        BinaryOp::Sub => sub(du, dv),
+        BinaryOp::Mul => add(mul(du, v), mul(u, dv)),
+        BinaryOp::Div => div(sub(mul(du, v.clone()), mul(u, dv)), pow(v, num(2.0))),
+        // u % v is u - v * q where q = (u - u % v) / v is a constant by pieces
+        BinaryOp::Rem => {
+            let q = div(sub(u.clone(), Expr::binary(BinaryOp::Rem, u, v.clone())), v);
+            sub(du, mul(dv, q))
+        }
+        BinaryOp::Pow if !r.uses_var(var) => mul(mul(v.clone(), pow(u, sub(v, num(1.0)))), du),
+        BinaryOp::Pow if !l.uses_var(var) => {
+            mul(mul(call(Func::Ln, vec![u.clone()]), pow(u, v)), dv)
+        }
+        BinaryOp::Pow => mul(
+            pow(u.clone(), v.clone()),
+            add(mul(dv, call(Func::Ln, vec![u.clone()])), div(mul(v, du), u)),
+        ),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge | BinaryOp::Eq | BinaryOp::Ne => {
+            num(0.0)
+        }
+    }
+}
+
+fn diff_call(f: Func, args: &[Expr], var: &str, unit: AngleUnit) -> Expr {
+    if let Func::Max | Func::Min = f {
+        return diff_extremum(f, args, var, unit);
+    }
+
+    if f == Func::Atan2 {
+        // atan2(y, x) is the angle of the point (x, y)
+        let (y, x) = (args[0].clone(), args[1].clone());
+        let (dy, dx) = (diff(&args[0], var, unit), diff(&args[1], var, unit));
+        return in_unit(
+            f,
+            div(
+                sub(mul(x.clone(), dy), mul(y.clone(), dx)),
+                add(pow(x, num(2.0)), pow(y, num(2.0))),
+            ),
+            unit,
+        );
+    }
+
+    if let Func::If | Func::Piecewise = f {
+        // Conditions sit at the even positions, except for a last value without condition
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(i, a)| {
+                if i % 2 == 0 && i + 1 < args.len() {
+                    a.clone()
+                } else {
+                    diff(a, var, unit)
+                }
+            })
+            .collect();
+        return call(f, args);
+    }
+
+    if f == Func::Clamp {
+        let (x, lo, hi) = (args[0].clone(), args[1].clone(), args[2].clone());
+        let below = Expr::binary(BinaryOp::Lt, x.clone(), lo);
+        let above = Expr::binary(BinaryOp::Gt, x, hi);
+        let inside = call(
+            Func::If,
+            vec![above, diff(&args[2], var, unit), diff(&args[0], var, unit)],
+        );
+        return call(Func::If, vec![below, diff(&args[1], var, unit), inside]);
+    }
+
+    let u = args[0].clone();
+    let sq = |e: Expr| pow(e, num(2.0));
+    let outer = match f {
+        Func::Sqrt => div(num(1.0), mul(num(2.0), call(Func::Sqrt, vec![u]))),
+        Func::Exp => call(Func::Exp, vec![u]),
+        Func::Ln => div(num(1.0), u),
+        Func::Abs => call(Func::Signum, vec![u]),
+        Func::Sin => call(Func::Cos, vec![u]),
+        Func::Cos => neg(call(Func::Sin, vec![u])),
+        Func::Tan => div(num(1.0), sq(call(Func::Cos, vec![u]))),
+        Func::Asin => div(num(1.0), call(Func::Sqrt, vec![sub(num(1.0), sq(u))])),
+        Func::Acos => neg(div(num(1.0), call(Func::Sqrt, vec![sub(num(1.0), sq(u))]))),
+        Func::Atan => div(num(1.0), add(num(1.0), sq(u))),
+        Func::Sinh => call(Func::Cosh, vec![u]),
+        Func::Cosh => call(Func::Sinh, vec![u]),
+        Func::Tanh => div(num(1.0), sq(call(Func::Cosh, vec![u]))),
+        Func::Asinh => div(num(1.0), call(Func::Sqrt, vec![add(sq(u), num(1.0))])),
+        Func::Acosh => div(num(1.0), call(Func::Sqrt, vec![sub(sq(u), num(1.0))])),
+        Func::Atanh => div(num(1.0), sub(num(1.0), sq(u))),
+        Func::Floor | Func::Ceil | Func::Round | Func::Signum | Func::Step => num(0.0),
+        Func::Atan2 | Func::Max | Func::Min | Func::If | Func::Clamp | Func::Piecewise => {
+            unreachable!()
+        }
+    };
+
+    mul(in_unit(f, outer, unit), diff(&args[0], var, unit))
+}
+
+/// `derivative` of `f` with respect to its argument in radians turned into the derivative with
+/// angles in `unit`
+fn in_unit(f: Func, derivative: Expr, unit: AngleUnit) -> Expr {
+    match unit.factor() {
+        Some(factor) if f.takes_angle() => mul(derivative, factor),
+        Some(factor) if f.gives_angle() => div(derivative, factor),
+        _ => derivative,
+    }
+}
+
+/// Derivative of `max` or `min`, seen as nested calls with two arguments
+fn diff_extremum(f: Func, args: &[Expr], var: &str, unit: AngleUnit) -> Expr {
+    let (last, rest) = match args.split_last() {
+        Some((last, rest)) if !rest.is_empty() => (last, rest),
+        Some((last, _)) => return diff(last, var, unit),
+        None => return num(f64::NAN),
+    };
+
+    let a = match rest {
+        [a] => a.clone(),
+        _ => call(f, rest.to_vec()),
+    };
+    let da = diff_extremum(f, rest, var, unit);
+    let db = diff(last, var, unit);
+
+    // max(a, b) = (a + b + |a - b|) / 2 and min(a, b) = (a + b - |a - b|) / 2
+    let sign = call(Func::Signum, vec![sub(a, last.clone())]);
+    let jump = mul(sign, sub(da.clone(), db.clone()));
+    let sum = add(da, db);
+    let total = if f == Func::Max {
+        add(sum, jump)
+    } else {
+        sub(sum, jump)
+    };
+
+    div(total, num(2.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::*;
+    use crate::formula::{parse, to_radians, Function};
+
+    fn derived(src: &str) -> String {
+        derivative(&parse(src).unwrap(), "x", AngleUnit::Radians).to_string()
+    }
+
+    #[test]
+    fn test_simplified() {
+        assert_eq!(derived("3"), "0");
+        assert_eq!(derived("y * x"), "y");
+        assert_eq!(derived("x^2"), "2 * x");
+        assert_eq!(derived("x^3 - 2 * x"), "3 * x^2 - 2");
+        assert_eq!(derived("sin(x)"), "cos(x)");
+        assert_eq!(derived("-cos(2 * x)"), "sin(2 * x) * 2");
+        assert_eq!(derived("ln(x)"), "1 / x");
+        assert_eq!(derived("floor(x)"), "0");
+        assert_eq!(derived("{x < 0: x^2; 3 * x}"), "{x < 0: 2 * x; 3}");
+        assert_eq!(derived("step(x) + (x > 1)"), "0");
+    }
+
+    #[test]
+    fn test_numeric() {
+        let formulas = [
+            "x^2 * sin(x) / (1 + x)",
+            "exp(-x^2) + sqrt(x) - abs(x - 3)",
+            "tan(x) + asin(x / 4) + acos(x / 5) + atan(x)",
+            "sinh(x) * cosh(x) - tanh(x) + asinh(x) + acosh(x + 2) + atanh(x / 3)",
+            "x^x + 2^x + atan2(x, x^2 + 1)",
+            "max(x, 2 - x, x^2 / 4) + min(sin(x), 0.5) + x % 0.7",
+            "if(x > 1, x^2, -x) + clamp(x^3, 0.1, x + 1) + {x < 2: sin(x); cos(x)} + step(x)",
+        ];
+
+        for src in formulas {
+            let f = Function::parse(src, &["x"]).unwrap();
+            let df = Function::new(derivative(f.expr(), "x", AngleUnit::Radians), &["x"]).unwrap();
+
+            for x in [0.3, 0.9, 1.7, 2.3] {
+                let h = 1e-6;
+                let expected = (f.eval(&[x + h]) - f.eval(&[x - h])) / (2.0 * h);
+                assert!(
+                    approx_eq!(f64, df.eval(&[x]), expected, epsilon = 1e-4),
+                    "d/dx {} at {}: {} != {}",
+                    src,
+                    x,
+                    df.eval(&[x]),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_degrees() {
+        let unit = AngleUnit::Degrees;
+        let expr = parse("sin(x)").unwrap();
+        assert_eq!(
+            derivative(&expr, "x", unit).to_string(),
+            "cos(x) * (pi / 180)"
+        );
+
+        let expr = parse("x * cos(x) + tan(30) * x + atan2(x, 2) - acos(x / 100)").unwrap();
+        let f = Function::new(to_radians(expr.clone(), unit), &["x"]).unwrap();
+        let df = to_radians(derivative(&expr, "x", unit), unit);
+        let df = Function::new(df, &["x"]).unwrap();
+
+        for x in [5.0, 40.0, 70.0] {
+            let h = 1e-4;
+            let expected = (f.eval(&[x + h]) - f.eval(&[x - h])) / (2.0 * h);
+            assert!(approx_eq!(f64, df.eval(&[x]), expected, epsilon = 1e-6));
+        }
+    }
+
+    #[test]
+    fn test_printable() {
+        let expr = parse("x^-x / (1 - x) + max(x, 1, -x)").unwrap();
+        let printed = derivative(&expr, "x", AngleUnit::Radians).to_string();
+
+        assert_eq!(parse(&printed).unwrap().to_string(), printed);
+    }
+}
@@ -0,0 +1,65 @@
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub enum Severity {
+    /// The formula cannot be shot
+    Error,
+    /// The formula can be shot but it probably does not do what the player wants
+    Warning,
+}
+
+/// A problem found in a formula. `start` and `end` are byte offsets in the formula source.
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct Diagnostic {
+    pub start: usize,
+    pub end: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(start: usize, end: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            start,
+            end,
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    pub fn warning(start: usize, end: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            start,
+            end,
+            severity: Severity::Warning,
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// All the diagnostics of a formula, in source order
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn has_errors(&self) -> bool {
+        self.0.iter().any(|d| d.is_error())
+    }
+}
+
+impl From<Error> for Diagnostic {
+    fn from(e: Error) -> Diagnostic {
+        Diagnostic::error(e.span.start, e.span.end, e.kind.to_string())
+    }
+}
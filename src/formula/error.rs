@@ -0,0 +1,174 @@
+use std::fmt;
+
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::JsValue;
+
+use super::{Feature, Span};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+#[serde(tag = "kind", content = "data")]
+pub enum ErrorKind {
+    /// The formula has nothing to evaluate
+    Empty,
+    /// A character that cannot start any token
+    UnexpectedChar(char),
+    InvalidNumber(String),
+    /// A token that does not fit where it was found
+    UnexpectedToken,
+    UnexpectedEnd,
+    UnclosedParen,
+    UnopenedParen,
+    /// An absolute value opened with `|` and never closed
+    UnclosedAbs,
+    /// A piecewise expression opened with `{` and never closed
+    UnclosedBrace,
+    UnknownIdentifier(String),
+    UnknownFunction(String),
+    /// A function called with a number of arguments it does not accept
+    WrongArity {
+        name: String,
+        expected: String,
+        found: usize,
+    },
+    /// A function used as if it was a variable
+    MissingArguments(String),
+    /// A list of formulas with the wrong number of entries
+    WrongComponentCount {
+        expected: usize,
+        found: usize,
+    },
+    /// The source of the formula is longer than allowed
+    TooLong {
+        max: usize,
+    },
+    /// The expression is nested deeper than allowed
+    TooDeep {
+        max: usize,
+    },
+    /// The expression has more nodes than allowed
+    TooManyNodes {
+        max: usize,
+    },
+    /// The complexity score of the formula is higher than allowed
+    TooComplex {
+        complexity: usize,
+        max: usize,
+    },
+    /// A known function that the game does not allow
+    ForbiddenFunction(String),
+    /// A feature of the syntax that the game does not allow
+    ForbiddenFeature(Feature),
+    /// The formula is not a polynomial of a low enough degree
+    DegreeTooHigh {
+        max: usize,
+    },
+    /// A LaTeX command that formulas do not support
+    UnsupportedCommand(String),
+    /// A helper named like a built-in function or constant
+    ReservedName(String),
+    /// A helper with two parameters of the same name
+    DuplicateParameter(String),
+    /// A helper that ends up calling itself
+    CyclicDefinition(String),
+}
+
+/// An error found while reading a formula, pointing at the part of the source that caused it
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+#[serde(rename = "FormulaError")]
+pub struct Error {
+    pub span: Span,
+    pub kind: ErrorKind,
+}
+
+impl Error {
+    pub fn new(span: Span, kind: ErrorKind) -> Error {
+        Error { span, kind }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Empty => write!(f, "The formula is empty"),
+            ErrorKind::UnexpectedChar(c) => write!(f, "Unexpected character `{}`", c),
+            ErrorKind::InvalidNumber(n) => write!(f, "`{}` is not a valid number", n),
+            ErrorKind::UnexpectedToken => write!(f, "Unexpected symbol"),
+            ErrorKind::UnexpectedEnd => write!(f, "The formula ends too early"),
+            ErrorKind::UnclosedParen => write!(f, "This parenthesis is never closed"),
+            ErrorKind::UnopenedParen => write!(f, "This parenthesis is never opened"),
+            ErrorKind::UnclosedAbs => write!(f, "This absolute value is never closed"),
+            ErrorKind::UnclosedBrace => write!(f, "This brace is never closed"),
+            ErrorKind::UnknownIdentifier(name) => write!(f, "Unknown identifier `{}`", name),
+            ErrorKind::UnknownFunction(name) => write!(f, "Unknown function `{}`", name),
+            ErrorKind::WrongArity {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` takes {} argument(s) but {} were given",
+                name, expected, found
+            ),
+            ErrorKind::MissingArguments(name) => {
+                write!(f, "`{}` is a function and needs arguments", name)
+            }
+            ErrorKind::WrongComponentCount { expected, found } => write!(
+                f,
+                "Expected {} comma separated formula(s) but found {}",
+                expected, found
+            ),
+            ErrorKind::TooLong { max } => {
+                write!(f, "The formula is longer than {} characters", max)
+            }
+            ErrorKind::TooDeep { max } => {
+                write!(f, "The formula is nested more than {} levels deep", max)
+            }
+            ErrorKind::TooManyNodes { max } => {
+                write!(f, "The formula has more than {} terms", max)
+            }
+            ErrorKind::TooComplex { complexity, max } => write!(
+                f,
+                "The formula has a complexity of {} but at most {} is allowed",
+                complexity, max
+            ),
+            ErrorKind::ForbiddenFunction(name) => {
+                write!(f, "The function `{}` is not allowed in this game", name)
+            }
+            ErrorKind::ForbiddenFeature(feature) => {
+                write!(f, "{} is not allowed in this game", feature.description())
+            }
+            ErrorKind::DegreeTooHigh { max } => {
+                write!(f, "This must be a polynomial of degree at most {}", max)
+            }
+            ErrorKind::UnsupportedCommand(name) => {
+                write!(f, "The LaTeX command `\\{}` is not supported", name)
+            }
+            ErrorKind::ReservedName(name) => write!(f, "`{}` is a reserved name", name),
+            ErrorKind::DuplicateParameter(name) => {
+                write!(f, "The parameter `{}` is declared twice", name)
+            }
+            ErrorKind::CyclicDefinition(name) => {
+                write!(f, "`{}` calls itself through its definition", name)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (at {}..{})",
+            self.kind, self.span.start, self.span.end
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for JsValue {
+    fn from(e: Error) -> JsValue {
+        serde_wasm_bindgen::to_value(&e).unwrap_or_else(|_| JsValue::from_str(&e.to_string()))
+    }
+}
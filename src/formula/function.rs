@@ -0,0 +1,162 @@
+use super::builtin::{constant, Func};
+use super::{parse, Error, ErrorKind, Expr, ExprKind, UnaryOp};
+
+/// An expression whose identifiers have all been resolved, ready to be evaluated with values for
+/// its variables
+#[derive(Clone, Debug)]
+pub struct Function {
+    expr: Expr,
+    vars: Vec<String>,
+}
+
+impl Function {
+    /// Binds `expr` to the variables `vars`, in the order their values will be given to
+    /// [`Function::eval`]
+    pub fn new(expr: Expr, vars: &[&str]) -> Result<Function, Error> {
+        if let Some(e) = check(&expr, vars).into_iter().next() {
+            return Err(e);
+        }
+
+        Ok(Function {
+            expr,
+            vars: vars.iter().map(|&v| v.into()).collect(),
+        })
+    }
+
+    /// Parses `src` and binds it to the variables `vars`
+    pub fn parse(src: &str, vars: &[&str]) -> Result<Function, Error> {
+        Function::new(parse(src)?, vars)
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn vars(&self) -> &Vec<String> {
+        &self.vars
+    }
+
+    /// Evaluates the function, `args` holds one value per variable
+    pub fn eval(&self, args: &[f64]) -> f64 {
+        self.eval_expr(&self.expr, args)
+    }
+
+    fn eval_expr(&self, expr: &Expr, args: &[f64]) -> f64 {
+        match &expr.kind {
+            ExprKind::Number(v) => *v,
+            ExprKind::Var(name) => match self.vars.iter().position(|v| v == name) {
+                Some(i) => args[i],
+                None => constant(name).unwrap_or(f64::NAN),
+            },
+            ExprKind::Unary(UnaryOp::Neg, e) => -self.eval_expr(e, args),
+            ExprKind::Binary(op, l, r) => {
+                op.apply(self.eval_expr(l, args), self.eval_expr(r, args))
+            }
+            ExprKind::Call(name, call_args) => {
+                let values: Vec<f64> = call_args.iter().map(|a| self.eval_expr(a, args)).collect();
+                Func::from_name(name).map_or(f64::NAN, |f| f.apply(&values))
+            }
+        }
+    }
+}
+
+/// Every identifier of `expr` that is neither one of `vars`, a constant nor a correctly called
+/// function
+pub fn check(expr: &Expr, vars: &[&str]) -> Vec<Error> {
+    let mut errors = Vec::new();
+
+    expr.walk(&mut |e| match &e.kind {
+        ExprKind::Var(name) => {
+            if vars.contains(&name.as_str()) || constant(name).is_some() {
+                return;
+            }
+
+            let kind = if Func::from_name(name).is_some() {
+                ErrorKind::MissingArguments(name.clone())
+            } else {
+                ErrorKind::UnknownIdentifier(name.clone())
+            };
+            errors.push(Error::new(e.span, kind));
+        }
+        ExprKind::Call(name, args) => match Func::from_name(name) {
+            Some(f) if !f.arity().accepts(args.len()) => errors.push(Error::new(
+                e.span,
+                ErrorKind::WrongArity {
+                    name: name.clone(),
+                    expected: f.arity().to_string(),
+                    found: args.len(),
+                },
+            )),
+            Some(_) => {}
+            None => errors.push(Error::new(e.span, ErrorKind::UnknownFunction(name.clone()))),
+        },
+        _ => {}
+    });
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::*;
+    use crate::formula::Span;
+
+    #[test]
+    fn test_eval() {
+        let f =
+            Function::parse("2 * x^2 - y / 4 + sin(pi / 2) + max(1, x, 3)", &["x", "y"]).unwrap();
+        assert!(approx_eq!(f64, f.eval(&[2.0, 8.0]), 8.0 - 2.0 + 1.0 + 3.0));
+    }
+
+    #[test]
+    fn test_eval_conditions() {
+        let eval = |src, x| Function::parse(src, &["x"]).unwrap().eval(&[x]);
+
+        assert_eq!(
+            eval("(x < 1) + (x >= 1) * 2 + (x == 1) * 4 + (x != 1) * 8", 1.0),
+            6.0
+        );
+        assert_eq!(eval("if(x > 0, x, -2x)", -3.0), 6.0);
+        assert_eq!(eval("step(x) + step(-x)", 0.0), 2.0);
+        assert_eq!(eval("clamp(x, -1, 1)", 3.0), 1.0);
+        assert!(eval("max(1, sqrt(x))", -1.0).is_nan());
+        assert!(eval("min(sqrt(x), 1, 2)", -1.0).is_nan());
+        assert!(eval("clamp(sqrt(x), -1, 1)", -1.0).is_nan());
+        assert!(eval("clamp(0, sqrt(x), 1)", -1.0).is_nan());
+
+        let f = Function::parse("{x < 0: x^2; x < 2: -x}", &["x"]).unwrap();
+        assert_eq!(f.eval(&[-2.0]), 4.0);
+        assert_eq!(f.eval(&[1.0]), -1.0);
+        assert!(f.eval(&[3.0]).is_nan());
+        assert_eq!(eval("{x < 0: 1; 2}", 0.0), 2.0);
+        assert!(eval("if(sqrt(x) > 1, 1, 2)", -1.0).is_nan());
+    }
+
+    #[test]
+    fn test_check() {
+        let expr = parse("x + y + sin + atan2(x) + foo(1)").unwrap();
+        let kinds: Vec<_> = check(&expr, &["x"])
+            .into_iter()
+            .map(|e| (e.span, e.kind))
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (Span::new(4, 5), ErrorKind::UnknownIdentifier("y".into())),
+                (Span::new(8, 11), ErrorKind::MissingArguments("sin".into())),
+                (
+                    Span::new(14, 22),
+                    ErrorKind::WrongArity {
+                        name: "atan2".into(),
+                        expected: "2".into(),
+                        found: 1
+                    }
+                ),
+                (Span::new(25, 31), ErrorKind::UnknownFunction("foo".into())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::builtin::{constant, Func};
+use super::{check, parse_definition, Error, ErrorKind, Expr, ExprKind, Limits, Span};
+
+/// A function defined by a player, like `bump(x, c) = exp(-(x - c)^2)`
+#[derive(Clone, Debug, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct Definition {
+    pub name: String,
+    pub params: Vec<String>,
+    /// The definition as the player wrote it
+    pub source: String,
+    #[serde(skip)]
+    body: Expr,
+}
+
+/// The helper functions a player has defined, which their formulas can call like built-in
+/// functions. Calls are inlined before the formula is checked, so a formula using helpers obeys
+/// the same limits as the formula written out in full.
+#[derive(Clone, Debug, Default, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct Helpers {
+    definitions: Vec<Definition>,
+}
+
+impl Helpers {
+    pub fn new() -> Helpers {
+        Helpers::default()
+    }
+
+    pub fn definitions(&self) -> &Vec<Definition> {
+        &self.definitions
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Definition> {
+        self.definitions.iter().find(|d| d.name == name)
+    }
+
+    /// Adds the helper defined by `src`, replacing any previous helper with the same name. The
+    /// helper may call the helpers already defined but not itself, and once expanded it must fit
+    /// within `limits` like any other formula.
+    pub fn define(&mut self, src: &str, limits: &Limits) -> Result<(), Error> {
+        limits.check_source(src)?;
+        let ((name, name_span), params, body) = parse_definition(src)?;
+
+        let mut param_names: Vec<String> = Vec::new();
+        for (p, span) in params {
+            if is_reserved(&p) {
+                return Err(Error::new(span, ErrorKind::ReservedName(p)));
+            }
+            if param_names.contains(&p) {
+                return Err(Error::new(span, ErrorKind::DuplicateParameter(p)));
+            }
+            param_names.push(p);
+        }
+
+        if is_reserved(&name) {
+            return Err(Error::new(name_span, ErrorKind::ReservedName(name)));
+        }
+
+        let definition = Definition {
+            name,
+            params: param_names,
+            source: src.into(),
+            body,
+        };
+
+        let mut helpers = self.clone();
+        match helpers
+            .definitions
+            .iter_mut()
+            .find(|d| d.name == definition.name)
+        {
+            Some(d) => *d = definition.clone(),
+            None => helpers.definitions.push(definition.clone()),
+        }
+
+        if let Some(span) = helpers.cycle(&definition) {
+            return Err(Error::new(
+                span,
+                ErrorKind::CyclicDefinition(definition.name),
+            ));
+        }
+
+        let expanded = helpers.expand(definition.body.clone(), limits)?;
+        if let Some(e) = limits
+            .check(std::slice::from_ref(&expanded))
+            .into_iter()
+            .next()
+        {
+            return Err(e);
+        }
+        let params: Vec<&str> = definition.params.iter().map(|p| p.as_str()).collect();
+        if let Some(e) = check(&expanded, &params).into_iter().next() {
+            return Err(e);
+        }
+
+        *self = helpers;
+        Ok(())
+    }
+
+    /// Removes the helper called `name`, returning false if there was none. Helpers calling it
+    /// stay defined but formulas using them fail until it is defined again.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.definitions.len();
+        self.definitions.retain(|d| d.name != name);
+        self.definitions.len() != len
+    }
+
+    /// Replaces every call to a helper in `expr` by the body of the helper. The nodes coming from
+    /// the body take the span of the call, so that errors point at the formula being expanded.
+    pub fn expand(&self, expr: Expr, limits: &Limits) -> Result<Expr, Error> {
+        let span = expr.span;
+        let kind = match expr.kind {
+            kind @ (ExprKind::Number(_) | ExprKind::Var(_)) => kind,
+            ExprKind::Unary(op, e) => ExprKind::Unary(op, Box::new(self.expand(*e, limits)?)),
+            ExprKind::Binary(op, l, r) => ExprKind::Binary(
+                op,
+                Box::new(self.expand(*l, limits)?),
+                Box::new(self.expand(*r, limits)?),
+            ),
+            ExprKind::Call(name, args) => {
+                let args = args
+                    .into_iter()
+                    .map(|a| self.expand(a, limits))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let definition = match self.get(&name) {
+                    Some(d) => d,
+                    None => return Ok(Expr::new(ExprKind::Call(name, args), span)),
+                };
+
+                if args.len() != definition.params.len() {
+                    return Err(Error::new(
+                        span,
+                        ErrorKind::WrongArity {
+                            name,
+                            expected: definition.params.len().to_string(),
+                            found: args.len(),
+                        },
+                    ));
+                }
+
+                // Spans in the body point into the source of the helper, not the formula
+                let body = self
+                    .expand(definition.body.clone(), limits)
+                    .map_err(|e| Error::new(span, e.kind))?;
+                let inlined = substitute(&body, &definition.params, &args, span);
+
+                if inlined.size() > limits.max_nodes() {
+                    return Err(Error::new(
+                        span,
+                        ErrorKind::TooManyNodes {
+                            max: limits.max_nodes(),
+                        },
+                    ));
+                }
+
+                return Ok(inlined);
+            }
+        };
+
+        Ok(Expr::new(kind, span))
+    }
+
+    /// Span of the call in the body of `definition` through which it ends up calling itself
+    fn cycle(&self, definition: &Definition) -> Option<Span> {
+        let mut visited = Vec::new();
+        self.calls(&definition.body)
+            .into_iter()
+            .find(|(name, _)| self.reaches(name, &definition.name, &mut visited))
+            .map(|(_, span)| span)
+    }
+
+    /// True if the helper `from` is `target` or calls it, directly or through other helpers
+    fn reaches(&self, from: &str, target: &str, visited: &mut Vec<String>) -> bool {
+        if from == target {
+            return true;
+        }
+        if visited.iter().any(|v| v == from) {
+            return false;
+        }
+        visited.push(from.into());
+
+        match self.get(from) {
+            Some(d) => self
+                .calls(&d.body)
+                .into_iter()
+                .any(|(name, _)| self.reaches(&name, target, visited)),
+            None => false,
+        }
+    }
+
+    /// Names and spans of the calls to helpers in `expr`
+    fn calls(&self, expr: &Expr) -> Vec<(String, Span)> {
+        let mut calls = Vec::new();
+        expr.walk(&mut |e| {
+            if let ExprKind::Call(name, _) = &e.kind {
+                if self.get(name).is_some() {
+                    calls.push((name.clone(), e.span));
+                }
+            }
+        });
+        calls
+    }
+}
+
+/// Names that cannot be given to helpers or their parameters
+fn is_reserved(name: &str) -> bool {
+    Func::from_name(name).is_some() || constant(name).is_some()
+}
+
+/// Copy of `body` where the parameters `params` are replaced by `args` and every other node is
+/// placed at `span`
+fn substitute(body: &Expr, params: &[String], args: &[Expr], span: Span) -> Expr {
+    let kind = match &body.kind {
+        ExprKind::Var(name) => match params.iter().position(|p| p == name) {
+            Some(i) => return args[i].clone(),
+            None => ExprKind::Var(name.clone()),
+        },
+        ExprKind::Number(v) => ExprKind::Number(*v),
+        ExprKind::Unary(op, e) => ExprKind::Unary(*op, Box::new(substitute(e, params, args, span))),
+        ExprKind::Binary(op, l, r) => ExprKind::Binary(
+            *op,
+            Box::new(substitute(l, params, args, span)),
+            Box::new(substitute(r, params, args, span)),
+        ),
+        ExprKind::Call(name, call_args) => ExprKind::Call(
+            name.clone(),
+            call_args
+                .iter()
+                .map(|a| substitute(a, params, args, span))
+                .collect(),
+        ),
+    };
+
+    Expr::new(kind, span)
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::*;
+    use crate::formula::{parse, Function};
+
+    fn expanded(helpers: &Helpers, src: &str) -> String {
+        helpers
+            .expand(parse(src).unwrap(), &Limits::default())
+            .unwrap()
+            .to_string()
+    }
+
+    fn kind(helpers: &mut Helpers, src: &str) -> ErrorKind {
+        helpers.define(src, &Limits::default()).unwrap_err().kind
+    }
+
+    #[test]
+    fn test_define() {
+        let limits = Limits::default();
+        let mut helpers = Helpers::new();
+        helpers.define("g(x) = x^2 - 3", &limits).unwrap();
+        helpers
+            .define("bump(x, c) = exp(-(x - c)^2)", &limits)
+            .unwrap();
+        helpers.define("h(t) = g(t) * bump(t, 1)", &limits).unwrap();
+
+        assert_eq!(expanded(&helpers, "g(2 * x) + 1"), "(2 * x)^2 - 3 + 1");
+        assert_eq!(expanded(&helpers, "bump(x, 3)"), "exp(-(x - 3)^2)");
+
+        let expr = helpers.expand(parse("h(x)").unwrap(), &limits).unwrap();
+        let f = Function::new(expr, &["x"]).unwrap();
+        assert!(approx_eq!(f64, f.eval(&[2.0]), 1.0 * (-1.0f64).exp()));
+
+        helpers.define("g(x) = x", &limits).unwrap();
+        assert_eq!(helpers.definitions().len(), 3);
+        assert_eq!(expanded(&helpers, "g(y)"), "y");
+        assert_eq!(helpers.get("g").unwrap().source, "g(x) = x");
+
+        assert!(helpers.remove("bump"));
+        assert!(!helpers.remove("bump"));
+        assert_eq!(expanded(&helpers, "bump(x, 1)"), "bump(x, 1)");
+    }
+
+    #[test]
+    fn test_cycles() {
+        let mut helpers = Helpers::new();
+        helpers.define("g(x) = x + 1", &Limits::default()).unwrap();
+        helpers
+            .define("f(x) = 2 * g(x)", &Limits::default())
+            .unwrap();
+
+        let err = helpers
+            .define("g(x) = f(x) - 1", &Limits::default())
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::CyclicDefinition("g".into()));
+        assert_eq!(err.span, Span::new(7, 11));
+        assert_eq!(
+            kind(&mut helpers, "k(x) = k(x - 1)"),
+            ErrorKind::CyclicDefinition("k".into())
+        );
+
+        // The failed definitions change nothing
+        assert_eq!(expanded(&helpers, "f(x)"), "2 * (x + 1)");
+        assert!(helpers.get("k").is_none());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut helpers = Helpers::new();
+        helpers
+            .define("g(x, y) = x * y", &Limits::default())
+            .unwrap();
+
+        assert_eq!(
+            kind(&mut helpers, "sin(x) = x"),
+            ErrorKind::ReservedName("sin".into())
+        );
+        assert_eq!(
+            kind(&mut helpers, "f(pi) = 2"),
+            ErrorKind::ReservedName("pi".into())
+        );
+        assert_eq!(
+            kind(&mut helpers, "f(x, x) = x"),
+            ErrorKind::DuplicateParameter("x".into())
+        );
+        assert_eq!(
+            kind(&mut helpers, "f(x) = y"),
+            ErrorKind::UnknownIdentifier("y".into())
+        );
+        assert_eq!(
+            kind(&mut helpers, "f(x) = g(x)"),
+            ErrorKind::WrongArity {
+                name: "g".into(),
+                expected: "2".into(),
+                found: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut limits = Limits::default();
+        limits.set_max_nodes(12);
+        limits.set_functions(vec!["sin".into()]).unwrap();
+
+        let mut helpers = Helpers::new();
+        helpers.define("g(x) = x * x * x", &limits).unwrap();
+
+        let err = helpers.define("f(x) = g(g(x))", &limits).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TooManyNodes { max: 12 });
+
+        let err = helpers.define("f(x) = cos(x)", &limits).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ForbiddenFunction("cos".into()));
+
+        let err = helpers
+            .expand(parse("1 + g(g(x))").unwrap(), &limits)
+            .unwrap_err();
+        assert_eq!(err.span, Span::new(4, 11));
+
+        // The error is in the body of h, but it points at the call to h
+        helpers
+            .define("h(x) = g(x) + 1", &Limits::default())
+            .unwrap();
+        limits.set_max_nodes(4);
+        let err = helpers
+            .expand(parse("2 * h(x)").unwrap(), &limits)
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TooManyNodes { max: 4 });
+        assert_eq!(err.span, Span::new(4, 8));
+    }
+}
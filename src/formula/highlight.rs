@@ -0,0 +1,287 @@
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::lexer::{scan, TokenKind};
+use super::{latex, Func, Helpers, Span};
+
+/// What a token of a formula is, for syntax highlighting
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub enum TokenCategory {
+    Number,
+    /// A variable, a constant or an unknown name
+    Identifier,
+    /// A known function, including the helpers of the player
+    Function,
+    Operator,
+    /// Parentheses, braces, the bars of absolute values and their LaTeX equivalents
+    Paren,
+    /// A part of the source that cannot be read as a token
+    Error,
+}
+
+/// A token of a formula. `start` and `end` are byte offsets in the formula source.
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct SyntaxToken {
+    pub category: TokenCategory,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    /// Index of the bracket paired with this one, if this is a bracket and it is balanced
+    pub matching: Option<usize>,
+}
+
+/// All the tokens of a formula, in source order
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct SyntaxTokens(pub Vec<SyntaxToken>);
+
+/// Role of a token in the nesting of the formula. Brackets of the same pair share a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Bracket {
+    Open(char),
+    Close(char),
+    /// `|`, which opens or closes an absolute value depending on what comes before
+    Bar,
+}
+
+/// A token reduced to what highlighting needs
+pub(super) struct Classified {
+    pub category: TokenCategory,
+    pub span: Span,
+    pub bracket: Option<Bracket>,
+}
+
+/// Tokenizes `formula` for syntax highlighting, see [`highlight`]. No helper counts as a function.
+#[wasm_bindgen(js_name = "tokenizeFormula")]
+pub fn tokenize_formula(formula: &str) -> SyntaxTokens {
+    highlight(formula, &Helpers::new())
+}
+
+/// Splits `src` into the tokens the parser reads, plain or LaTeX, classified for syntax
+/// highlighting. The calls to `helpers` count as functions. Unlike parsing, tokenizing never
+/// fails: the parts that cannot be read become error tokens and the rest is tokenized anyway.
+pub fn highlight(src: &str, helpers: &Helpers) -> SyntaxTokens {
+    let classified = if latex::is_latex(src) {
+        latex::classify(src)
+    } else {
+        classify(src, helpers)
+    };
+    let matching = match_brackets(&classified);
+
+    let tokens = classified
+        .iter()
+        .zip(matching)
+        .map(|(c, matching)| SyntaxToken {
+            category: c.category,
+            start: c.span.start,
+            end: c.span.end,
+            text: src[c.span.start..c.span.end].into(),
+            matching,
+        })
+        .collect();
+
+    SyntaxTokens(tokens)
+}
+
+fn classify(src: &str, helpers: &Helpers) -> Vec<Classified> {
+    scan(src)
+        .into_iter()
+        .map(|token| {
+            let token = match token {
+                Ok(token) => token,
+                Err(e) => {
+                    return Classified {
+                        category: TokenCategory::Error,
+                        span: e.span,
+                        bracket: None,
+                    }
+                }
+            };
+
+            let (category, bracket) = match &token.kind {
+                TokenKind::Number(_) | TokenKind::Superscript(_) => (TokenCategory::Number, None),
+                TokenKind::Ident(name)
+                    if Func::from_name(name).is_some() || helpers.get(name).is_some() =>
+                {
+                    (TokenCategory::Function, None)
+                }
+                TokenKind::Ident(_) => (TokenCategory::Identifier, None),
+                TokenKind::LParen => (TokenCategory::Paren, Some(Bracket::Open('('))),
+                TokenKind::RParen => (TokenCategory::Paren, Some(Bracket::Close('('))),
+                TokenKind::LBrace => (TokenCategory::Paren, Some(Bracket::Open('{'))),
+                TokenKind::RBrace => (TokenCategory::Paren, Some(Bracket::Close('{'))),
+                TokenKind::Pipe => (TokenCategory::Paren, Some(Bracket::Bar)),
+                _ => (TokenCategory::Operator, None),
+            };
+
+            Classified {
+                category,
+                span: token.span,
+                bracket,
+            }
+        })
+        .collect()
+}
+
+/// Index of the bracket paired with each token. A bar closes the innermost absolute value when
+/// it follows an operand, like the parser does, and opens a new one otherwise.
+fn match_brackets(tokens: &[Classified]) -> Vec<Option<usize>> {
+    let mut matching = vec![None; tokens.len()];
+    let mut open: Vec<(usize, Option<char>)> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let after_operand = i > 0
+            && match (tokens[i - 1].category, tokens[i - 1].bracket) {
+                (_, Some(Bracket::Close(_))) => true,
+                (_, Some(Bracket::Bar)) => matching[i - 1].is_some_and(|j| j < i - 1),
+                (TokenCategory::Number | TokenCategory::Identifier, _) => true,
+                _ => false,
+            };
+
+        let key = match token.bracket {
+            Some(Bracket::Open(c)) => {
+                open.push((i, Some(c)));
+                continue;
+            }
+            Some(Bracket::Bar) if !(after_operand && matches!(open.last(), Some((_, None)))) => {
+                open.push((i, None));
+                continue;
+            }
+            Some(Bracket::Bar) => None,
+            Some(Bracket::Close(c)) => Some(c),
+            None => continue,
+        };
+
+        // A closing bracket that does not match the innermost open one is left unpaired
+        if let Some(&(j, _)) = open.last().filter(|(_, k)| *k == key) {
+            open.pop();
+            matching[i] = Some(j);
+            matching[j] = Some(i);
+        }
+    }
+
+    matching
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::Limits;
+
+    fn categories(src: &str) -> Vec<(&str, TokenCategory)> {
+        let tokens = tokenize_formula(src).0;
+        tokens
+            .iter()
+            .map(|t| (&src[t.start..t.end], t.category))
+            .collect()
+    }
+
+    fn matching(src: &str) -> Vec<Option<usize>> {
+        tokenize_formula(src).0.iter().map(|t| t.matching).collect()
+    }
+
+    #[test]
+    fn test_categories() {
+        use TokenCategory::*;
+
+        assert_eq!(
+            categories("2.5x² + sin(pi) # foo(1e3) <= ."),
+            vec![
+                ("2.5", Number),
+                ("x", Identifier),
+                ("²", Number),
+                ("+", Operator),
+                ("sin", Function),
+                ("(", Paren),
+                ("pi", Identifier),
+                (")", Paren),
+                ("#", Error),
+                ("foo", Identifier),
+                ("(", Paren),
+                ("1e3", Number),
+                (")", Paren),
+                ("<=", Operator),
+                (".", Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_helpers() {
+        let mut helpers = Helpers::new();
+        helpers
+            .define("bump(x) = exp(-x^2)", &Limits::default())
+            .unwrap();
+
+        let tokens = highlight("bump(x)", &helpers).0;
+        assert_eq!(tokens[0].category, TokenCategory::Function);
+        assert_eq!(tokens[0].text, "bump");
+    }
+
+    #[test]
+    fn test_matching() {
+        assert_eq!(
+            matching("sin((x) + {x < 0: 1})"),
+            vec![
+                None,
+                Some(13),
+                Some(4),
+                None,
+                Some(2),
+                None,
+                Some(12),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(6),
+                Some(1),
+            ]
+        );
+        assert_eq!(
+            matching("||x| - 1|"),
+            vec![Some(6), Some(3), None, Some(1), None, None, Some(0)]
+        );
+        assert_eq!(
+            matching("|x||y|"),
+            vec![Some(2), None, Some(0), Some(5), None, Some(3)]
+        );
+        assert_eq!(matching("(x]"), vec![None, None, None]);
+        assert_eq!(matching("(x))"), vec![Some(2), None, Some(0), None]);
+    }
+
+    #[test]
+    fn test_latex() {
+        use TokenCategory::*;
+
+        let src = r"\frac{1}{2}\sin\left(x\right) + \log|x|";
+        assert_eq!(
+            categories(src),
+            vec![
+                (r"\frac", Function),
+                ("{", Paren),
+                ("1", Number),
+                ("}", Paren),
+                ("{", Paren),
+                ("2", Number),
+                ("}", Paren),
+                (r"\sin", Function),
+                (r"\left", Paren),
+                ("(", Paren),
+                ("x", Identifier),
+                (r"\right", Paren),
+                (")", Paren),
+                ("+", Operator),
+                (r"\log", Error),
+                ("|", Paren),
+                ("x", Identifier),
+                ("|", Paren),
+            ]
+        );
+        assert_eq!(tokenize_formula(src).0[9].matching, Some(12));
+    }
+}
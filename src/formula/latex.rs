@@ -0,0 +1,838 @@
+use super::highlight::{Bracket, Classified};
+use super::parser::MAX_NESTING;
+use super::{BinaryOp, Error, ErrorKind, Expr, ExprKind, Func, Span, TokenCategory, UnaryOp};
+
+/// True if `src` is written in LaTeX rather than in the plain formula syntax, which never uses
+/// backslashes
+pub fn is_latex(src: &str) -> bool {
+    src.contains('\\')
+}
+
+/// Parses `src`, written in LaTeX, as a list of comma separated expressions. The expressions
+/// are the same as the ones of the equivalent plain formula, with spans in the LaTeX source.
+///
+/// The supported subset covers what students usually type for a function of one variable:
+///
+/// - Numbers, single letter variables with optional subscripts like `x_1`, `y'` for the
+///   variable `dy`, `\pi`, `\theta` and the other Greek letters.
+/// - `+`, `-`, `*`, `\cdot`, `\times`, `/`, `\div`, `^`, implicit products like `2x\sin x`,
+///   comparisons and `\frac`, `\dfrac`, `\tfrac`, `\sqrt` with an optional index.
+/// - Parentheses, brackets and braces for grouping, with or without `\left` and `\right`,
+///   `|x|`, `\lfloor x\rfloor` and `\lceil x\rceil`.
+/// - The functions `\sin`, `\cos`, `\tan`, `\arcsin`, `\arccos`, `\arctan`, `\sinh`, `\cosh`,
+///   `\tanh`, `\ln`, `\exp`, `\max` and `\min`, with or without parentheses, and any other
+///   function with `\operatorname{name}`. `\sin^2 x` is the square of `\sin x`.
+///
+/// Spacing commands like `\,` or `\quad` are ignored.
+pub fn parse_latex(src: &str) -> Result<Vec<Expr>, Error> {
+    let mut parser = Parser::new(src)?;
+
+    let mut exprs = vec![parser.expr()?];
+    while parser.eat_sym(',').is_some() {
+        exprs.push(parser.expr()?);
+    }
+
+    match parser.peek() {
+        None => Ok(exprs),
+        Some(t) => Err(Error::new(t.span, ErrorKind::UnexpectedToken)),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    /// The digits of a number, which may be split when only its first digit is an argument
+    Number(String),
+    Letter(char),
+    /// A command without its backslash, like `frac`
+    Command(String),
+    Symbol(char),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+/// Commands that only change the spacing of the formula
+const SPACES: &[&str] = &[",", ":", ";", "!", " ", "quad", "qquad"];
+
+pub(super) const GREEK: &[&str] = &[
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa",
+    "lambda", "mu", "nu", "xi", "rho", "sigma", "tau", "phi", "chi", "psi", "omega",
+];
+
+/// Functions with a command of their own, and the name of the matching function
+pub(super) const FUNCTIONS: &[(&str, Func)] = &[
+    ("sin", Func::Sin),
+    ("cos", Func::Cos),
+    ("tan", Func::Tan),
+    ("arcsin", Func::Asin),
+    ("arccos", Func::Acos),
+    ("arctan", Func::Atan),
+    ("sinh", Func::Sinh),
+    ("cosh", Func::Cosh),
+    ("tanh", Func::Tanh),
+    ("ln", Func::Ln),
+    ("exp", Func::Exp),
+    ("max", Func::Max),
+    ("min", Func::Min),
+];
+
+fn tokenize(src: &str) -> Result<Vec<Token>, Error> {
+    scan(src).into_iter().collect()
+}
+
+/// Splits `src` into tokens, going on after the parts that cannot be read, which are given as
+/// errors in their place
+fn scan(src: &str) -> Vec<Result<Token, Error>> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < src.len() {
+        let start = pos;
+
+        match scan_token(src, &mut pos) {
+            Ok(None) => {}
+            Ok(Some(kind)) => tokens.push(Ok(Token {
+                kind,
+                span: Span::new(start, pos),
+            })),
+            Err(e) => tokens.push(Err(e)),
+        }
+    }
+
+    tokens
+}
+
+/// Kind of the token starting at `pos`, which is moved to its end, or `None` for whitespace and
+/// spacing commands. `pos` moves past the part that cannot be read on errors too.
+fn scan_token(src: &str, pos: &mut usize) -> Result<Option<TokenKind>, Error> {
+    let bytes = src.as_bytes();
+    let start = *pos;
+    let c = src[start..].chars().next().unwrap();
+    *pos += c.len_utf8();
+
+    let kind = match c {
+        c if c.is_whitespace() => return Ok(None),
+        '0'..='9' | '.' => {
+            while *pos < bytes.len() && (bytes[*pos].is_ascii_digit() || bytes[*pos] == b'.') {
+                *pos += 1;
+            }
+            let text = &src[start..*pos];
+            if text.parse::<f64>().is_err() {
+                return Err(Error::new(
+                    Span::new(start, *pos),
+                    ErrorKind::InvalidNumber(text.into()),
+                ));
+            }
+            TokenKind::Number(text.into())
+        }
+        c if c.is_ascii_alphabetic() => TokenKind::Letter(c),
+        '\\' => {
+            while *pos < bytes.len() && bytes[*pos].is_ascii_alphabetic() {
+                *pos += 1;
+            }
+            // Commands made of a single other character, like `\,`
+            if *pos == start + 1 {
+                match src[*pos..].chars().next() {
+                    Some(c) => *pos += c.len_utf8(),
+                    None => {
+                        return Err(Error::new(
+                            Span::new(start, *pos),
+                            ErrorKind::UnexpectedChar('\\'),
+                        ))
+                    }
+                }
+            }
+
+            let name = &src[start + 1..*pos];
+            if SPACES.contains(&name) {
+                return Ok(None);
+            }
+            TokenKind::Command(name.into())
+        }
+        '+' | '-' | '*' | '/' | '^' | '_' | '(' | ')' | '[' | ']' | '{' | '}' | '|' | ',' | '<'
+        | '>' | '=' | '\'' => TokenKind::Symbol(c),
+        _ => {
+            return Err(Error::new(
+                Span::new(start, *pos),
+                ErrorKind::UnexpectedChar(c),
+            ))
+        }
+    };
+
+    Ok(Some(kind))
+}
+
+/// Tokens of `src` classified for syntax highlighting, see [`super::highlight`]
+pub(super) fn classify(src: &str) -> Vec<Classified> {
+    let classified = |category, span, bracket| Classified {
+        category,
+        span,
+        bracket,
+    };
+
+    scan(src)
+        .into_iter()
+        .map(|token| {
+            let token = match token {
+                Ok(token) => token,
+                Err(e) => return classified(TokenCategory::Error, e.span, None),
+            };
+
+            let (category, bracket) = match &token.kind {
+                TokenKind::Number(_) => (TokenCategory::Number, None),
+                TokenKind::Letter(_) => (TokenCategory::Identifier, None),
+                TokenKind::Symbol(c @ ('(' | '[' | '{')) => {
+                    (TokenCategory::Paren, Some(Bracket::Open(*c)))
+                }
+                TokenKind::Symbol(')') => (TokenCategory::Paren, Some(Bracket::Close('('))),
+                TokenKind::Symbol(']') => (TokenCategory::Paren, Some(Bracket::Close('['))),
+                TokenKind::Symbol('}') => (TokenCategory::Paren, Some(Bracket::Close('{'))),
+                TokenKind::Symbol('|') => (TokenCategory::Paren, Some(Bracket::Bar)),
+                TokenKind::Symbol(_) => (TokenCategory::Operator, None),
+                TokenKind::Command(c) => match c.as_str() {
+                    "frac" | "dfrac" | "tfrac" | "sqrt" | "operatorname" | "mathrm" => {
+                        (TokenCategory::Function, None)
+                    }
+                    c if FUNCTIONS.iter().any(|(f, _)| *f == c) => (TokenCategory::Function, None),
+                    c if c == "pi" || GREEK.contains(&c) => (TokenCategory::Identifier, None),
+                    "cdot" | "times" | "div" | "lt" | "gt" | "le" | "leq" | "ge" | "geq" | "ne"
+                    | "neq" => (TokenCategory::Operator, None),
+                    "left" | "right" => (TokenCategory::Paren, None),
+                    "{" => (TokenCategory::Paren, Some(Bracket::Open('B'))),
+                    "}" => (TokenCategory::Paren, Some(Bracket::Close('B'))),
+                    "lfloor" => (TokenCategory::Paren, Some(Bracket::Open('f'))),
+                    "rfloor" => (TokenCategory::Paren, Some(Bracket::Close('f'))),
+                    "lceil" => (TokenCategory::Paren, Some(Bracket::Open('c'))),
+                    "rceil" => (TokenCategory::Paren, Some(Bracket::Close('c'))),
+                    _ => (TokenCategory::Error, None),
+                },
+            };
+
+            classified(category, token.span, bracket)
+        })
+        .collect()
+}
+
+/// Value of a number token, whose text was checked when reading it. A part of the number that
+/// starts with a dot, like `.5` in `x^2.5`, is still a number.
+fn number(text: &str) -> f64 {
+    text.parse().unwrap_or(f64::NAN)
+}
+
+/// `expr` at `span`, as the argument of `func` if there is one
+fn wrap(func: Option<Func>, mut expr: Expr, span: Span) -> Expr {
+    match func {
+        Some(f) => Expr::new(ExprKind::Call(f.name().into(), vec![expr]), span),
+        None => {
+            expr.span = span;
+            expr
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Length of the source, to point at its end
+    len: usize,
+    /// Current recursion depth
+    depth: usize,
+    /// Number of absolute values opened with `|` and not closed yet
+    abs: usize,
+}
+
+impl Parser {
+    fn new(src: &str) -> Result<Parser, Error> {
+        let tokens = tokenize(src)?;
+
+        if tokens.is_empty() {
+            return Err(Error::new(Span::new(0, src.len()), ErrorKind::Empty));
+        }
+
+        Ok(Parser {
+            tokens,
+            pos: 0,
+            len: src.len(),
+            depth: 0,
+            abs: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn end(&self) -> Span {
+        Span::new(self.len, self.len)
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        match self.tokens.get(self.pos) {
+            Some(t) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => Err(Error::new(self.end(), ErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    /// Consumes the next token if it is the symbol `c`
+    fn eat_sym(&mut self, c: char) -> Option<Span> {
+        self.eat(&TokenKind::Symbol(c))
+    }
+
+    /// Consumes the next token if it is the command `name`
+    fn eat_command(&mut self, name: &str) -> Option<Span> {
+        self.eat(&TokenKind::Command(name.into()))
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> Option<Span> {
+        match self.peek() {
+            Some(t) if t.kind == *kind => {
+                let span = t.span;
+                self.pos += 1;
+                Some(span)
+            }
+            _ => None,
+        }
+    }
+
+    /// Consumes the closing symbol `c` of the group opened at `open`
+    fn close(&mut self, c: char, open: Span, unclosed: ErrorKind) -> Result<Span, Error> {
+        if let Some(span) = self.eat_sym(c) {
+            return Ok(span);
+        }
+
+        match self.peek() {
+            Some(t) => Err(Error::new(t.span, ErrorKind::UnexpectedToken)),
+            None => Err(Error::new(open, unclosed)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let lhs = self.sum()?;
+        let op = match self.comparison() {
+            Some(op) => op,
+            None => return Ok(lhs),
+        };
+
+        let rhs = self.sum()?;
+        let next = self.peek().map(|t| t.span);
+        match next {
+            Some(span) if self.comparison().is_some() => {
+                Err(Error::new(span, ErrorKind::UnexpectedToken))
+            }
+            _ => Ok(Expr::binary(op, lhs, rhs)),
+        }
+    }
+
+    /// The comparison operator of the next token, which is consumed, if it is one
+    fn comparison(&mut self) -> Option<BinaryOp> {
+        let op = match &self.peek()?.kind {
+            TokenKind::Symbol('<') => BinaryOp::Lt,
+            TokenKind::Symbol('>') => BinaryOp::Gt,
+            TokenKind::Symbol('=') => BinaryOp::Eq,
+            TokenKind::Command(c) => match c.as_str() {
+                "lt" => BinaryOp::Lt,
+                "gt" => BinaryOp::Gt,
+                "le" | "leq" => BinaryOp::Le,
+                "ge" | "geq" => BinaryOp::Ge,
+                "ne" | "neq" => BinaryOp::Ne,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        self.pos += 1;
+        Some(op)
+    }
+
+    fn sum(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let expr = self.sum_inner();
+        self.depth = depth;
+
+        expr
+    }
+
+    fn sum_inner(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.term()?;
+
+        loop {
+            let op = if self.eat_sym('+').is_some() {
+                BinaryOp::Add
+            } else if self.eat_sym('-').is_some() {
+                BinaryOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+
+            // The operands read so far go one level deeper, under the new operator
+            self.deeper()?;
+            let rhs = self.term()?;
+            lhs = Expr::binary(op, lhs, rhs);
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let expr = self.term_inner();
+        self.depth = depth;
+
+        expr
+    }
+
+    fn term_inner(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.unary()?;
+
+        loop {
+            let op = if self.eat_sym('*').is_some()
+                || self.eat_command("cdot").is_some()
+                || self.eat_command("times").is_some()
+            {
+                BinaryOp::Mul
+            } else if self.eat_sym('/').is_some() || self.eat_command("div").is_some() {
+                BinaryOp::Div
+            } else if self.starts_factor(true) {
+                BinaryOp::Mul
+            } else {
+                return Ok(lhs);
+            };
+
+            self.deeper()?;
+            let rhs = self.unary()?;
+            lhs = Expr::binary(op, lhs, rhs);
+        }
+    }
+
+    /// True if the next token starts a factor multiplied without an operator, not counting
+    /// function calls unless `functions` is set. Inside an absolute value a `|` closes it rather
+    /// than starting a new one.
+    fn starts_factor(&self, functions: bool) -> bool {
+        match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Letter(_)) => true,
+            Some(TokenKind::Symbol('(' | '[' | '{')) => true,
+            Some(TokenKind::Symbol('|')) => self.abs == 0,
+            Some(TokenKind::Command(c)) => match c.as_str() {
+                "right" | "rfloor" | "rceil" | "}" | "cdot" | "times" | "div" => false,
+                "lt" | "gt" | "le" | "leq" | "ge" | "geq" | "ne" | "neq" => false,
+                "operatorname" | "mathrm" => functions,
+                c if FUNCTIONS.iter().any(|(f, _)| *f == c) => functions,
+                // Unknown commands are reported when reading the factor
+                _ => true,
+            },
+            _ => false,
+        }
+    }
+
+    /// Goes one level deeper in the tree, failing if that nests more than `MAX_NESTING`
+    fn deeper(&mut self) -> Result<(), Error> {
+        if self.depth >= MAX_NESTING {
+            let span = self.peek().map_or(self.end(), |t| t.span);
+            return Err(Error::new(span, ErrorKind::TooDeep { max: MAX_NESTING }));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        self.deeper()?;
+        let expr = self.unary_inner();
+        self.depth -= 1;
+
+        expr
+    }
+
+    fn unary_inner(&mut self) -> Result<Expr, Error> {
+        if let Some(span) = self.eat_sym('-') {
+            let expr = self.unary()?;
+            let span = span.join(expr.span);
+            return Ok(Expr::new(
+                ExprKind::Unary(UnaryOp::Neg, Box::new(expr)),
+                span,
+            ));
+        }
+
+        if self.eat_sym('+').is_some() {
+            return self.unary();
+        }
+
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expr, Error> {
+        let base = self.atom()?;
+
+        if self.eat_sym('^').is_some() {
+            let exp = self.group()?;
+            return Ok(Expr::binary(BinaryOp::Pow, base, exp));
+        }
+
+        Ok(base)
+    }
+
+    /// Argument of a command or a power: a braced expression or a single symbol, like the `2`
+    /// of `x^2` or of `\frac12`
+    fn group(&mut self) -> Result<Expr, Error> {
+        if let Some(open) = self.eat_sym('{') {
+            let mut expr = self.expr()?;
+            let close = self.close('}', open, ErrorKind::UnclosedBrace)?;
+            expr.span = open.join(close);
+            return Ok(expr);
+        }
+
+        // Only the first digit of a number belongs to the group
+        if let Some(Token {
+            kind: TokenKind::Number(text),
+            span,
+        }) = self.peek().cloned()
+        {
+            if text.len() > 1 && !text.starts_with('.') {
+                let (digit, rest) = text.split_at(1);
+                self.tokens[self.pos] = Token {
+                    kind: TokenKind::Number(rest.into()),
+                    span: Span::new(span.start + 1, span.end),
+                };
+                return Ok(Expr::new(
+                    ExprKind::Number(number(digit)),
+                    Span::new(span.start, span.start + 1),
+                ));
+            }
+        }
+
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, Error> {
+        let token = self.next()?;
+
+        match token.kind {
+            TokenKind::Number(text) => Ok(Expr::new(ExprKind::Number(number(&text)), token.span)),
+            TokenKind::Letter(c) => self.variable(c.to_string(), token.span),
+            TokenKind::Symbol(c @ ('(' | '[' | '{')) => {
+                let (close, unclosed) = match c {
+                    '(' => (')', ErrorKind::UnclosedParen),
+                    '[' => (']', ErrorKind::UnclosedParen),
+                    _ => ('}', ErrorKind::UnclosedBrace),
+                };
+                let mut expr = self.expr()?;
+                let end = self.close(close, token.span, unclosed)?;
+                expr.span = token.span.join(end);
+                Ok(expr)
+            }
+            TokenKind::Symbol('|') => {
+                self.abs += 1;
+                let arg = self.expr();
+                self.abs -= 1;
+
+                let arg = arg?;
+                let end = self.close('|', token.span, ErrorKind::UnclosedAbs)?;
+                Ok(Expr::new(
+                    ExprKind::Call(Func::Abs.name().into(), vec![arg]),
+                    token.span.join(end),
+                ))
+            }
+            TokenKind::Symbol(')' | ']') => Err(Error::new(token.span, ErrorKind::UnopenedParen)),
+            TokenKind::Symbol(_) => Err(Error::new(token.span, ErrorKind::UnexpectedToken)),
+            TokenKind::Command(name) => self.command(&name, token.span),
+        }
+    }
+
+    /// A variable called `name`, with its optional subscript and prime
+    fn variable(&mut self, mut name: String, span: Span) -> Result<Expr, Error> {
+        let mut span = span;
+
+        if self.eat_sym('_').is_some() {
+            let sub = self.subscript()?;
+            name = format!("{}_{}", name, sub.0);
+            span = span.join(sub.1);
+        }
+
+        if let Some(prime) = self.eat_sym('\'') {
+            name = format!("d{}", name);
+            span = span.join(prime);
+        }
+
+        Ok(Expr::new(ExprKind::Var(name), span))
+    }
+
+    /// Text of a subscript made of letters and digits, like `1` or `{max}`
+    fn subscript(&mut self) -> Result<(String, Span), Error> {
+        let token = self.next()?;
+
+        match token.kind {
+            TokenKind::Letter(c) => Ok((c.to_string(), token.span)),
+            TokenKind::Number(text) if !text.contains('.') => Ok((text, token.span)),
+            TokenKind::Symbol('{') => {
+                let mut text = String::new();
+                loop {
+                    let t = self
+                        .next()
+                        .map_err(|_| Error::new(token.span, ErrorKind::UnclosedBrace))?;
+                    match t.kind {
+                        TokenKind::Letter(c) => text.push(c),
+                        TokenKind::Number(digits) if !digits.contains('.') => text += &digits,
+                        TokenKind::Symbol('}') if !text.is_empty() => {
+                            return Ok((text, token.span.join(t.span)))
+                        }
+                        _ => return Err(Error::new(t.span, ErrorKind::UnexpectedToken)),
+                    }
+                }
+            }
+            _ => Err(Error::new(token.span, ErrorKind::UnexpectedToken)),
+        }
+    }
+
+    fn command(&mut self, name: &str, span: Span) -> Result<Expr, Error> {
+        match name {
+            "pi" => Ok(Expr::new(ExprKind::Var("pi".into()), span)),
+            c if GREEK.contains(&c) => self.variable(c.into(), span),
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.group()?;
+                let den = self.group()?;
+                let span = span.join(den.span);
+                let mut expr = Expr::binary(BinaryOp::Div, num, den);
+                expr.span = span;
+                Ok(expr)
+            }
+            "sqrt" => {
+                let index = match self.eat_sym('[') {
+                    Some(open) => {
+                        let index = self.expr()?;
+                        self.close(']', open, ErrorKind::UnclosedParen)?;
+                        Some(index)
+                    }
+                    None => None,
+                };
+                let arg = self.group()?;
+                let span = span.join(arg.span);
+
+                let mut expr = match index {
+                    None => Expr::call(Func::Sqrt.name(), vec![arg]),
+                    Some(n) => Expr::binary(
+                        BinaryOp::Pow,
+                        arg,
+                        Expr::binary(BinaryOp::Div, Expr::new(ExprKind::Number(1.0), n.span), n),
+                    ),
+                };
+                expr.span = span;
+                Ok(expr)
+            }
+            "left" => {
+                let open = self.next()?;
+                let (close, func) = match open.kind {
+                    TokenKind::Symbol('(') => (TokenKind::Symbol(')'), None),
+                    TokenKind::Symbol('[') => (TokenKind::Symbol(']'), None),
+                    TokenKind::Symbol('|') => (TokenKind::Symbol('|'), Some(Func::Abs)),
+                    TokenKind::Command(c) if c == "{" => (TokenKind::Command("}".into()), None),
+                    TokenKind::Command(c) if c == "lfloor" => {
+                        (TokenKind::Command("rfloor".into()), Some(Func::Floor))
+                    }
+                    TokenKind::Command(c) if c == "lceil" => {
+                        (TokenKind::Command("rceil".into()), Some(Func::Ceil))
+                    }
+                    _ => return Err(Error::new(open.span, ErrorKind::UnexpectedToken)),
+                };
+
+                let abs = self.abs;
+                self.abs = 0;
+                let expr = self.expr();
+                self.abs = abs;
+                let expr = expr?;
+
+                let end = match (self.eat_command("right"), self.peek()) {
+                    (Some(_), Some(_)) => {
+                        let t = self.next()?;
+                        if t.kind != close {
+                            return Err(Error::new(t.span, ErrorKind::UnexpectedToken));
+                        }
+                        t.span
+                    }
+                    (None, Some(t)) => return Err(Error::new(t.span, ErrorKind::UnexpectedToken)),
+                    (_, None) => return Err(Error::new(span, ErrorKind::UnclosedParen)),
+                };
+
+                Ok(wrap(func, expr, span.join(end)))
+            }
+            "lfloor" | "lceil" => {
+                let (close, func) = match name {
+                    "lfloor" => ("rfloor", Func::Floor),
+                    _ => ("rceil", Func::Ceil),
+                };
+                let expr = self.expr()?;
+                match self.eat_command(close) {
+                    Some(end) => Ok(wrap(Some(func), expr, span.join(end))),
+                    None => match self.peek() {
+                        Some(t) => Err(Error::new(t.span, ErrorKind::UnexpectedToken)),
+                        None => Err(Error::new(span, ErrorKind::UnclosedParen)),
+                    },
+                }
+            }
+            "operatorname" | "mathrm" => {
+                let name = self.subscript()?.0;
+                self.function(name, span)
+            }
+            _ => match FUNCTIONS.iter().find(|(c, _)| *c == name) {
+                Some((_, f)) => self.function(f.name().into(), span),
+                None => Err(Error::new(span, ErrorKind::UnsupportedCommand(name.into()))),
+            },
+        }
+    }
+
+    /// Call to the function `name`, whose command is at `span`. The arguments are either between
+    /// parentheses or the product that follows, as in `\sin 2x`.
+    fn function(&mut self, name: String, span: Span) -> Result<Expr, Error> {
+        let power = match self.eat_sym('^') {
+            Some(_) => Some(self.group()?),
+            None => None,
+        };
+
+        let open = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Symbol('(')) => self.eat_sym('('),
+            Some(TokenKind::Command(c)) if c == "left" => {
+                match self.tokens.get(self.pos + 1).map(|t| &t.kind) {
+                    Some(TokenKind::Symbol('(')) => {
+                        self.pos += 1;
+                        self.eat_sym('(')
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let (args, end) = match open {
+            Some(open) => {
+                let mut args = vec![self.expr()?];
+                while self.eat_sym(',').is_some() {
+                    args.push(self.expr()?);
+                }
+                self.eat_command("right");
+                let end = self.close(')', open, ErrorKind::UnclosedParen)?;
+                (args, end)
+            }
+            None => {
+                let depth = self.depth;
+                let mut arg = self.unary()?;
+                while self.starts_factor(false) {
+                    self.deeper()?;
+                    let rhs = self.power()?;
+                    arg = Expr::binary(BinaryOp::Mul, arg, rhs);
+                }
+                self.depth = depth;
+                let end = arg.span;
+                (vec![arg], end)
+            }
+        };
+
+        let call = Expr::new(ExprKind::Call(name, args), span.join(end));
+        Ok(match power {
+            Some(p) => Expr::binary(BinaryOp::Pow, call, p),
+            None => call,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::parse;
+
+    fn translated(src: &str) -> String {
+        let exprs = parse_latex(src).unwrap();
+        let texts: Vec<String> = exprs.iter().map(|e| e.to_string()).collect();
+        texts.join(", ")
+    }
+
+    fn err(src: &str) -> (usize, usize, ErrorKind) {
+        let e = parse_latex(src).unwrap_err();
+        (e.span.start, e.span.end, e.kind)
+    }
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(
+            translated(r"\frac{1}{2}\sin\left(x\right)+\sqrt{x}"),
+            "1 / 2 * sin(x) + sqrt(x)"
+        );
+        assert_eq!(translated(r"x^{2} - 3x^2y"), "x^2 - 3 * x^2 * y");
+        assert_eq!(translated(r"\frac12 x^2"), "1 / 2 * x^2");
+        assert_eq!(
+            translated(r"2\pi\theta \cdot \sqrt[3]{x}"),
+            "2 * pi * theta * x^(1 / 3)"
+        );
+        assert_eq!(
+            translated(r"\sin 2x + \cos^2 x \tan x"),
+            "sin(2 * x) + cos(x)^2 * tan(x)"
+        );
+        assert_eq!(
+            translated(r"\left|x - 1\right| + |y|"),
+            "abs(x - 1) + abs(y)"
+        );
+        assert_eq!(
+            translated(r"\lfloor x \rfloor \div \left\lceil x\right\rceil"),
+            "floor(x) / ceil(x)"
+        );
+        assert_eq!(
+            translated(r"\max(x, 1) + \operatorname{bump}(x, 2)"),
+            "max(x, 1) + bump(x, 2)"
+        );
+        assert_eq!(translated(r"y' + x_1 + x_{12}"), "dy + x_1 + x_12");
+        assert_eq!(translated(r"\cos t,\, \sin t"), "cos(t), sin(t)");
+        assert_eq!(translated(r"\ln(x) \le 2"), "ln(x) <= 2");
+    }
+
+    #[test]
+    fn test_same_as_plain() {
+        let pairs = [
+            (r"\frac{x+1}{x-1}", "(x + 1) / (x - 1)"),
+            (r"e^{-x^2}", "e^(-x^2)"),
+            (r"\exp\left(-\frac{x}{2}\right)", "exp(-(x / 2))"),
+        ];
+
+        for (latex, plain) in pairs {
+            assert_eq!(parse_latex(latex).unwrap()[0], parse(plain).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            err(r"\log x"),
+            (0, 4, ErrorKind::UnsupportedCommand("log".into()))
+        );
+        assert_eq!(
+            err(r"x + \int x"),
+            (4, 8, ErrorKind::UnsupportedCommand("int".into()))
+        );
+        assert_eq!(err(r"\frac{1}{x"), (8, 9, ErrorKind::UnclosedBrace));
+        assert_eq!(err(r"\left( x"), (0, 5, ErrorKind::UnclosedParen));
+        assert_eq!(
+            err(r"\left( x \right]"),
+            (15, 16, ErrorKind::UnexpectedToken)
+        );
+        assert_eq!(err(r"x & y"), (2, 3, ErrorKind::UnexpectedChar('&')));
+        assert_eq!(err(r"\sqrt"), (5, 5, ErrorKind::UnexpectedEnd));
+        // Only the 2 is the exponent, and the 3 that follows is not a factor
+        assert_eq!(err(r"\pi x^23"), (7, 8, ErrorKind::UnexpectedToken));
+
+        let too_deep = ErrorKind::TooDeep { max: MAX_NESTING };
+        let long = format!(r"{}x", r"x \cdot ".repeat(10_000));
+        assert_eq!(parse_latex(&long).unwrap_err().kind, too_deep);
+        let long = format!(r"\sin {}", "x ".repeat(10_000));
+        assert_eq!(parse_latex(&long).unwrap_err().kind, too_deep);
+    }
+
+    #[test]
+    fn test_spans() {
+        let expr = &parse_latex(r"1 + \sqrt{x}").unwrap()[0];
+        match &expr.kind {
+            ExprKind::Binary(_, _, rhs) => assert_eq!(rhs.span, Span::new(4, 12)),
+            _ => panic!("Expected a sum"),
+        }
+    }
+}
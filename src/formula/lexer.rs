@@ -0,0 +1,338 @@
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::{Error, ErrorKind};
+
+/// Byte range `start..end` of the formula source
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Smallest span covering both spans
+    pub fn join(&self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Number(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    /// A power written with superscript digits, like `²` or `⁻¹`
+    Superscript(f64),
+    /// `|`, around the argument of an absolute value
+    Pipe,
+    LParen,
+    RParen,
+    Comma,
+    Equals,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    EqualEqual,
+    NotEqual,
+    /// `{`, opening a piecewise expression
+    LBrace,
+    RBrace,
+    Colon,
+    Semicolon,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits `src` into tokens, skipping whitespace. `π` is read as the constant `pi`.
+pub fn tokenize(src: &str) -> Result<Vec<Token>, Error> {
+    scan(src).into_iter().collect()
+}
+
+/// Splits `src` into tokens like [`tokenize`], but goes on after the parts that cannot be read,
+/// which are given as errors in their place
+pub(super) fn scan(src: &str) -> Vec<Result<Token, Error>> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < src.len() {
+        let start = pos;
+
+        match scan_token(src, &mut pos) {
+            Ok(None) => {}
+            Ok(Some(kind)) => tokens.push(Ok(Token {
+                kind,
+                span: Span::new(start, pos),
+            })),
+            Err(e) => tokens.push(Err(e)),
+        }
+    }
+
+    tokens
+}
+
+/// Kind of the token starting at `pos`, which is moved to its end, or `None` for whitespace.
+/// `pos` moves past the part that cannot be read on errors too.
+fn scan_token(src: &str, pos: &mut usize) -> Result<Option<TokenKind>, Error> {
+    let bytes = src.as_bytes();
+    let start = *pos;
+    let c = src[start..].chars().next().unwrap();
+
+    let kind = match c {
+        c if c.is_whitespace() => {
+            *pos += c.len_utf8();
+            return Ok(None);
+        }
+        '0'..='9' | '.' => {
+            *pos = scan_number(bytes, *pos);
+            let text = &src[start..*pos];
+            match text.parse() {
+                Ok(value) => TokenKind::Number(value),
+                Err(_) => {
+                    return Err(Error::new(
+                        Span::new(start, *pos),
+                        ErrorKind::InvalidNumber(text.into()),
+                    ))
+                }
+            }
+        }
+        c if c.is_ascii_alphabetic() || c == '_' => {
+            while *pos < bytes.len() && (bytes[*pos].is_ascii_alphanumeric() || bytes[*pos] == b'_')
+            {
+                *pos += 1;
+            }
+            TokenKind::Ident(src[start..*pos].into())
+        }
+        c if superscript(c).is_some() => {
+            // A minus sign can only start the exponent
+            let mut text = String::new();
+            while let Some(c) = src[*pos..].chars().next() {
+                match superscript(c) {
+                    Some('-') if !text.is_empty() => break,
+                    Some(digit) => text.push(digit),
+                    None => break,
+                }
+                *pos += c.len_utf8();
+            }
+            match text.parse() {
+                Ok(value) => TokenKind::Superscript(value),
+                Err(_) => {
+                    return Err(Error::new(
+                        Span::new(start, *pos),
+                        ErrorKind::InvalidNumber(src[start..*pos].into()),
+                    ))
+                }
+            }
+        }
+        '<' | '>' | '=' | '!' => {
+            *pos += 1;
+            let equal = bytes.get(*pos) == Some(&b'=');
+            if equal {
+                *pos += 1;
+            }
+
+            match (c, equal) {
+                ('<', false) => TokenKind::Less,
+                ('<', true) => TokenKind::LessEqual,
+                ('>', false) => TokenKind::Greater,
+                ('>', true) => TokenKind::GreaterEqual,
+                ('=', false) => TokenKind::Equals,
+                ('=', true) => TokenKind::EqualEqual,
+                ('!', true) => TokenKind::NotEqual,
+                _ => {
+                    return Err(Error::new(
+                        Span::new(start, *pos),
+                        ErrorKind::UnexpectedChar(c),
+                    ))
+                }
+            }
+        }
+        _ => {
+            *pos += c.len_utf8();
+            match c {
+                '+' => TokenKind::Plus,
+                '-' => TokenKind::Minus,
+                '*' => TokenKind::Star,
+                '/' => TokenKind::Slash,
+                '%' => TokenKind::Percent,
+                '^' => TokenKind::Caret,
+                '|' => TokenKind::Pipe,
+                'π' => TokenKind::Ident("pi".into()),
+                '(' => TokenKind::LParen,
+                ')' => TokenKind::RParen,
+                ',' => TokenKind::Comma,
+                '{' => TokenKind::LBrace,
+                '}' => TokenKind::RBrace,
+                ':' => TokenKind::Colon,
+                ';' => TokenKind::Semicolon,
+                _ => {
+                    return Err(Error::new(
+                        Span::new(start, *pos),
+                        ErrorKind::UnexpectedChar(c),
+                    ))
+                }
+            }
+        }
+    };
+
+    Ok(Some(kind))
+}
+
+/// The ASCII character written as the superscript `c`, if it is one
+fn superscript(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_digit(c as u32 - '⁰' as u32, 10),
+        '⁻' => Some('-'),
+        _ => None,
+    }
+}
+
+/// Returns the end of the number starting at `pos`. An exponent is only part of the number if
+/// digits follow it, so that `2e` is still the number 2 followed by the constant `e`.
+fn scan_number(bytes: &[u8], mut pos: usize) -> usize {
+    let digits = |mut pos: usize| {
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        pos
+    };
+
+    pos = digits(pos);
+    if pos < bytes.len() && bytes[pos] == b'.' {
+        pos = digits(pos + 1);
+    }
+
+    if pos < bytes.len() && (bytes[pos] == b'e' || bytes[pos] == b'E') {
+        let mut exp = pos + 1;
+        if exp < bytes.len() && (bytes[exp] == b'+' || bytes[exp] == b'-') {
+            exp += 1;
+        }
+        if exp < bytes.len() && bytes[exp].is_ascii_digit() {
+            pos = digits(exp);
+        }
+    }
+
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(src: &str) -> Vec<TokenKind> {
+        tokenize(src).unwrap().into_iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            kinds("2.5*sin(x_1)^-1e-2"),
+            vec![
+                TokenKind::Number(2.5),
+                TokenKind::Star,
+                TokenKind::Ident("sin".into()),
+                TokenKind::LParen,
+                TokenKind::Ident("x_1".into()),
+                TokenKind::RParen,
+                TokenKind::Caret,
+                TokenKind::Minus,
+                TokenKind::Number(1e-2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exponent_needs_digits() {
+        assert_eq!(
+            kinds("2e"),
+            vec![TokenKind::Number(2.0), TokenKind::Ident("e".into())]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let tokens = tokenize(" x +  10").unwrap();
+        let spans: Vec<_> = tokens.iter().map(|t| (t.span.start, t.span.end)).collect();
+        assert_eq!(spans, vec![(1, 2), (3, 4), (6, 8)]);
+    }
+
+    #[test]
+    fn test_unicode() {
+        assert_eq!(
+            kinds("2π|x|²⁻¹⁰"),
+            vec![
+                TokenKind::Number(2.0),
+                TokenKind::Ident("pi".into()),
+                TokenKind::Pipe,
+                TokenKind::Ident("x".into()),
+                TokenKind::Pipe,
+                TokenKind::Superscript(2.0),
+                TokenKind::Superscript(-10.0),
+            ]
+        );
+
+        let tokens = tokenize("x²³").unwrap();
+        assert_eq!(tokens[1].span, Span::new(1, 5));
+        assert_eq!(tokens[1].kind, TokenKind::Superscript(23.0));
+        assert_eq!(
+            tokenize("x⁻").unwrap_err().kind,
+            ErrorKind::InvalidNumber("⁻".into())
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(
+            kinds("{x<=1: 2; x!=y==z>0}"),
+            vec![
+                TokenKind::LBrace,
+                TokenKind::Ident("x".into()),
+                TokenKind::LessEqual,
+                TokenKind::Number(1.0),
+                TokenKind::Colon,
+                TokenKind::Number(2.0),
+                TokenKind::Semicolon,
+                TokenKind::Ident("x".into()),
+                TokenKind::NotEqual,
+                TokenKind::Ident("y".into()),
+                TokenKind::EqualEqual,
+                TokenKind::Ident("z".into()),
+                TokenKind::Greater,
+                TokenKind::Number(0.0),
+                TokenKind::RBrace,
+            ]
+        );
+        assert_eq!(kinds("x = 1")[1], TokenKind::Equals);
+        assert_eq!(
+            tokenize("!x").unwrap_err().kind,
+            ErrorKind::UnexpectedChar('!')
+        );
+    }
+
+    #[test]
+    fn test_unexpected_char() {
+        let err = tokenize("x # 2").unwrap_err();
+        assert_eq!(err.span, Span::new(2, 3));
+        assert_eq!(err.kind, ErrorKind::UnexpectedChar('#'));
+    }
+}
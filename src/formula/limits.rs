@@ -0,0 +1,520 @@
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use super::{complexity, constant, BinaryOp, Error, ErrorKind, Expr, ExprKind, Func, Span};
+
+/// A construct of the formula syntax that a game can forbid, beyond what polynomials need
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    /// `/` with a denominator that is not constant
+    Division,
+    /// `%`
+    Remainder,
+    /// `^` with an exponent that is not a whole number, like `x^0.5` or `2^x`
+    Powers,
+    /// `<`, `<=`, `>`, `>=`, `==` and `!=`
+    Comparisons,
+}
+
+impl Feature {
+    pub const ALL: &'static [Feature] = &[
+        Feature::Division,
+        Feature::Remainder,
+        Feature::Powers,
+        Feature::Comparisons,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Feature> {
+        Feature::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Division => "division",
+            Feature::Remainder => "remainder",
+            Feature::Powers => "powers",
+            Feature::Comparisons => "comparisons",
+        }
+    }
+
+    /// What the feature is, to tell the player what they cannot use
+    pub fn description(&self) -> &'static str {
+        match self {
+            Feature::Division => "Division by an expression that is not constant",
+            Feature::Remainder => "The remainder operator `%`",
+            Feature::Powers => "A power whose exponent is not a whole number",
+            Feature::Comparisons => "Comparisons",
+        }
+    }
+
+    /// The feature used by the node `expr` itself, not counting its children
+    fn used_by(expr: &Expr) -> Option<Feature> {
+        let (op, rhs) = match &expr.kind {
+            ExprKind::Binary(op, _, rhs) => (op, rhs),
+            _ => return None,
+        };
+
+        match op {
+            BinaryOp::Div if degree(rhs) != Some(0) => Some(Feature::Division),
+            BinaryOp::Rem => Some(Feature::Remainder),
+            BinaryOp::Pow if whole(rhs).is_none() => Some(Feature::Powers),
+            op if op.is_comparison() => Some(Feature::Comparisons),
+            _ => None,
+        }
+    }
+}
+
+/// Bounds on the formulas players can shoot, so that untrusted input cannot make the game
+/// exhaust its memory or time
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    max_length: usize,
+    max_depth: usize,
+    max_nodes: usize,
+    max_complexity: usize,
+    functions: Vec<Func>,
+    features: Vec<Feature>,
+    max_degree: usize,
+    max_steps: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_length: 256,
+            max_depth: 32,
+            max_nodes: 128,
+            max_complexity: usize::MAX,
+            functions: Func::ALL.to_vec(),
+            features: Feature::ALL.to_vec(),
+            max_degree: usize::MAX,
+            max_steps: 10_000_000,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Limits {
+    /// Limits with their default values
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Limits {
+        Limits::default()
+    }
+
+    /// Maximum length of a formula, in bytes
+    #[wasm_bindgen(getter)]
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_length(&mut self, max_length: usize) {
+        self.max_length = max_length;
+    }
+
+    /// Maximum nesting of the expression tree of a formula
+    #[wasm_bindgen(getter)]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Maximum number of nodes of the expression tree of a formula
+    #[wasm_bindgen(getter)]
+    pub fn max_nodes(&self) -> usize {
+        self.max_nodes
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_nodes(&mut self, max_nodes: usize) {
+        self.max_nodes = max_nodes;
+    }
+
+    /// Maximum complexity score of a formula, see [`complexity`]. There is no maximum by default.
+    #[wasm_bindgen(getter)]
+    pub fn max_complexity(&self) -> usize {
+        self.max_complexity
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_complexity(&mut self, max_complexity: usize) {
+        self.max_complexity = max_complexity;
+    }
+
+    /// Maximum number of operations evaluated during a single shot
+    #[wasm_bindgen(getter)]
+    pub fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+    }
+
+    /// Names of the functions formulas are allowed to call
+    #[wasm_bindgen(getter)]
+    pub fn functions(&self) -> Vec<String> {
+        self.functions.iter().map(|f| f.name().into()).collect()
+    }
+
+    /// Only allows calling the functions in `names`, which must all be known functions
+    #[wasm_bindgen(js_name = "setFunctions")]
+    pub fn set_functions(&mut self, names: Vec<String>) -> Result<(), String> {
+        self.functions = names
+            .iter()
+            .map(|n| Func::from_name(n).ok_or_else(|| format!("Unknown function `{}`", n)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(())
+    }
+
+    /// Names of the constructs formulas are allowed to use, see [`Feature`]
+    #[wasm_bindgen(getter)]
+    pub fn features(&self) -> Vec<String> {
+        self.features.iter().map(|f| f.name().into()).collect()
+    }
+
+    /// Only allows using the constructs in `names`, which must all be known features. Sums,
+    /// products, division by constants and whole powers are always allowed.
+    #[wasm_bindgen(js_name = "setFeatures")]
+    pub fn set_features(&mut self, names: Vec<String>) -> Result<(), String> {
+        self.features = names
+            .iter()
+            .map(|n| Feature::from_name(n).ok_or_else(|| format!("Unknown feature `{}`", n)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(())
+    }
+
+    /// Maximum degree of a formula seen as a polynomial in its variables. Calls to functions
+    /// have the degree of their arguments, so that `abs(2 * x + 1)` has degree 1. A formula
+    /// that is not a polynomial, like `1 / x`, has no degree and breaks any maximum. There is no
+    /// maximum by default.
+    #[wasm_bindgen(getter)]
+    pub fn max_degree(&self) -> usize {
+        self.max_degree
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_degree(&mut self, max_degree: usize) {
+        self.max_degree = max_degree;
+    }
+}
+
+impl Limits {
+    pub fn allowed_functions(&self) -> &Vec<Func> {
+        &self.functions
+    }
+
+    pub fn allowed_features(&self) -> &Vec<Feature> {
+        &self.features
+    }
+
+    /// Checks the limits that apply to the source, before parsing it
+    pub fn check_source(&self, src: &str) -> Result<(), Error> {
+        if src.len() > self.max_length {
+            return Err(Error::new(
+                Span::new(self.max_length, src.len()),
+                ErrorKind::TooLong {
+                    max: self.max_length,
+                },
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Every limit broken by the parsed components of a formula
+    pub fn check(&self, exprs: &[Expr]) -> Vec<Error> {
+        let mut errors = Vec::new();
+
+        let span = exprs
+            .iter()
+            .map(|e| e.span)
+            .reduce(|a, b| a.join(b))
+            .unwrap_or_default();
+
+        let nodes: usize = exprs.iter().map(|e| e.size()).sum();
+        if nodes > self.max_nodes {
+            errors.push(Error::new(
+                span,
+                ErrorKind::TooManyNodes {
+                    max: self.max_nodes,
+                },
+            ));
+        }
+
+        let complexity = complexity(exprs);
+        if complexity > self.max_complexity {
+            errors.push(Error::new(
+                span,
+                ErrorKind::TooComplex {
+                    complexity,
+                    max: self.max_complexity,
+                },
+            ));
+        }
+
+        for expr in exprs {
+            if let Some(deep) = self.too_deep(expr, 1) {
+                errors.push(Error::new(
+                    deep.span,
+                    ErrorKind::TooDeep {
+                        max: self.max_depth,
+                    },
+                ));
+            }
+
+            expr.walk(&mut |e| {
+                // Unknown functions are reported when resolving the formula
+                let func = match &e.kind {
+                    ExprKind::Call(name, _) => Func::from_name(name),
+                    _ => None,
+                };
+
+                if let Some(f) = func.filter(|f| !self.functions.contains(f)) {
+                    errors.push(Error::new(
+                        e.span,
+                        ErrorKind::ForbiddenFunction(f.name().into()),
+                    ));
+                }
+
+                if let Some(f) = Feature::used_by(e).filter(|f| !self.features.contains(f)) {
+                    errors.push(Error::new(e.span, ErrorKind::ForbiddenFeature(f)));
+                }
+            });
+
+            if self.max_degree != usize::MAX {
+                if let Some(e) = too_high_degree(expr, self.max_degree) {
+                    errors.push(Error::new(
+                        e.span,
+                        ErrorKind::DegreeTooHigh {
+                            max: self.max_degree,
+                        },
+                    ));
+                }
+            }
+        }
+
+        errors.sort_by_key(|e| e.span.start);
+        errors
+    }
+
+    /// First node found deeper than the maximum depth, `depth` being the depth of `expr`
+    fn too_deep<'a>(&self, expr: &'a Expr, depth: usize) -> Option<&'a Expr> {
+        if depth > self.max_depth {
+            return Some(expr);
+        }
+
+        expr.children()
+            .into_iter()
+            .find_map(|c| self.too_deep(c, depth + 1))
+    }
+}
+
+/// Degree of `expr` as a polynomial in its variables, `None` if it is not a polynomial. Named
+/// constants and calls with constant arguments have degree 0. `abs` has the degree of its
+/// argument, so that games can allow piecewise linear formulas, and any other call is not a
+/// polynomial.
+fn degree(expr: &Expr) -> Option<usize> {
+    let max_of = |exprs: &[&Expr]| {
+        exprs
+            .iter()
+            .map(|e| degree(e))
+            .try_fold(0, |acc, d| d.map(|d| acc.max(d)))
+    };
+
+    match &expr.kind {
+        ExprKind::Number(_) => Some(0),
+        ExprKind::Var(name) if constant(name).is_some() => Some(0),
+        ExprKind::Var(_) => Some(1),
+        ExprKind::Unary(_, e) => degree(e),
+        ExprKind::Binary(op, l, r) => match op {
+            BinaryOp::Mul => degree(l)?.checked_add(degree(r)?),
+            BinaryOp::Div if degree(r)? == 0 => degree(l),
+            BinaryOp::Div | BinaryOp::Rem => match (degree(l)?, degree(r)?) {
+                (0, 0) => Some(0),
+                _ => None,
+            },
+            BinaryOp::Pow => match (degree(l)?, whole(r)) {
+                (d, Some(n)) => d.checked_mul(n),
+                (0, None) if degree(r)? == 0 => Some(0),
+                _ => None,
+            },
+            _ => max_of(&[l, r]),
+        },
+        ExprKind::Call(name, args) => match max_of(&args.iter().collect::<Vec<_>>())? {
+            0 => Some(0),
+            d if Func::from_name(name) == Some(Func::Abs) => Some(d),
+            _ => None,
+        },
+    }
+}
+
+/// Value of `expr` if it is a whole number written as is
+fn whole(expr: &Expr) -> Option<usize> {
+    match expr.kind {
+        ExprKind::Number(v) if v >= 0.0 && v.fract() == 0.0 && v <= u32::MAX as f64 => {
+            Some(v as usize)
+        }
+        _ => None,
+    }
+}
+
+/// Smallest part of `expr` that is not a polynomial of degree at most `max`
+fn too_high_degree(expr: &Expr, max: usize) -> Option<&Expr> {
+    expr.children()
+        .into_iter()
+        .find_map(|c| too_high_degree(c, max))
+        .or_else(|| match degree(expr) {
+            Some(d) if d <= max => None,
+            _ => Some(expr),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::parse_list;
+
+    fn kinds(limits: &Limits, src: &str) -> Vec<ErrorKind> {
+        limits
+            .check(&parse_list(src).unwrap())
+            .into_iter()
+            .map(|e| e.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_source_length() {
+        let limits = Limits {
+            max_length: 5,
+            ..Limits::default()
+        };
+
+        assert!(limits.check_source("x + 1").is_ok());
+        assert_eq!(
+            limits.check_source("x + 10").unwrap_err().span,
+            Span::new(5, 6)
+        );
+    }
+
+    #[test]
+    fn test_size() {
+        let limits = Limits {
+            max_depth: 3,
+            max_nodes: 6,
+            ..Limits::default()
+        };
+
+        assert!(kinds(&limits, "x * 2 + 1, x").is_empty());
+        assert_eq!(
+            kinds(&limits, "x * 2 + 1, x, x"),
+            vec![ErrorKind::TooManyNodes { max: 6 }]
+        );
+        assert_eq!(
+            kinds(&limits, "-(-(-x))"),
+            vec![ErrorKind::TooDeep { max: 3 }]
+        );
+    }
+
+    #[test]
+    fn test_complexity() {
+        let mut limits = Limits::default();
+        assert!(kinds(&limits, "1.23456789 * x^2.5").is_empty());
+
+        limits.set_max_complexity(10);
+        assert!(kinds(&limits, "2 * sin(x) + 1").is_empty());
+        assert_eq!(
+            kinds(&limits, "1.23456789 * x^2.5"),
+            vec![ErrorKind::TooComplex {
+                complexity: 14,
+                max: 10
+            }]
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut limits = Limits::default();
+        limits.set_functions(vec!["sin".into()]).unwrap();
+
+        assert!(kinds(&limits, "sin(x)").is_empty());
+        assert_eq!(
+            kinds(&limits, "sin(cos(x))"),
+            vec![ErrorKind::ForbiddenFunction("cos".into())]
+        );
+        assert!(limits.set_functions(vec!["foo".into()]).is_err());
+    }
+
+    #[test]
+    fn test_features() {
+        let mut limits = Limits::default();
+        limits.set_features(vec!["comparisons".into()]).unwrap();
+
+        assert!(kinds(&limits, "x^2 / 2 - 3 * x / pi + (x < 0)").is_empty());
+        assert_eq!(
+            kinds(&limits, "1 / x + x % 2 + x^0.5 + 2^x"),
+            vec![
+                ErrorKind::ForbiddenFeature(Feature::Division),
+                ErrorKind::ForbiddenFeature(Feature::Remainder),
+                ErrorKind::ForbiddenFeature(Feature::Powers),
+                ErrorKind::ForbiddenFeature(Feature::Powers),
+            ]
+        );
+        assert!(limits.set_features(vec!["foo".into()]).is_err());
+    }
+
+    #[test]
+    fn test_degree() {
+        // Quadratics only
+        let mut limits = Limits::default();
+        limits.set_max_degree(2);
+        limits.set_functions(Vec::new()).unwrap();
+        limits.set_features(Vec::new()).unwrap();
+
+        assert!(kinds(&limits, "(x - 1) * (x + 2) / 3 + pi^2").is_empty());
+        assert_eq!(
+            kinds(&limits, "x^2 * (x + 1)"),
+            vec![ErrorKind::DegreeTooHigh { max: 2 }]
+        );
+        let errors = limits.check(&parse_list("1 + x * x * x").unwrap());
+        assert_eq!(errors[0].span, Span::new(4, 13));
+
+        // Functions of the variables are not polynomials, even when allowed
+        limits
+            .set_functions(vec!["sin".into(), "exp".into()])
+            .unwrap();
+        assert!(kinds(&limits, "sin(2) * x^2 + exp(pi)").is_empty());
+        assert_eq!(
+            kinds(&limits, "sin(x)"),
+            vec![ErrorKind::DegreeTooHigh { max: 2 }]
+        );
+        let errors = limits.check(&parse_list("x + exp(2 * x)").unwrap());
+        assert_eq!(errors[0].span, Span::new(4, 14));
+
+        // Linear and absolute values
+        limits.set_max_degree(1);
+        limits.set_functions(vec!["abs".into()]).unwrap();
+        assert!(kinds(&limits, "abs(2 * x + 1) - x / 2").is_empty());
+        assert_eq!(
+            kinds(&limits, "abs(x) * x"),
+            vec![ErrorKind::DegreeTooHigh { max: 1 }]
+        );
+
+        // Non polynomials have no degree
+        limits.set_features(vec!["division".into()]).unwrap();
+        assert_eq!(
+            kinds(&limits, "1 / x"),
+            vec![ErrorKind::DegreeTooHigh { max: 1 }]
+        );
+    }
+}
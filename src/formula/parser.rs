@@ -0,0 +1,568 @@
+use super::lexer::{tokenize, Token, TokenKind};
+use super::{BinaryOp, Error, ErrorKind, Expr, ExprKind, Func, Span, UnaryOp};
+
+/// Maximum nesting of parentheses, unary operators, powers and chained binary operators the
+/// parser accepts, to keep the depth of the trees it builds, and so the recursion of everything
+/// walking them, bounded whatever the input
+pub(super) const MAX_NESTING: usize = 128;
+
+/// Parses `src` into an expression tree. Only the syntax is checked, identifiers are resolved
+/// later when the expression is turned into a [`super::Function`].
+///
+/// A product may leave out the `*` when its right factor starts with an identifier, a
+/// parenthesis or an absolute value, as in `2x`, `3sin(x)` or `(x + 1)(x - 2)`. An identifier
+/// followed by a parenthesis is still a call, so `x(x + 1)` needs the `*`.
+///
+/// Comparisons bind the weakest and cannot be chained, so `0 < x < 1` must be written with
+/// both conditions apart, for example `{0 < x: x < 1; 0}`.
+///
+/// ```text
+/// expr   := sum (('<' | '<=' | '>' | '>=' | '==' | '!=') sum)?
+/// sum    := term (('+' | '-') term)*
+/// term   := unary (('*' | '/' | '%')? unary)*
+/// unary  := ('-' | '+') unary | power
+/// power  := atom ('^' unary | superscript)?
+/// atom   := number | ident | ident '(' expr (',' expr)* ')' | '(' expr ')' | '|' expr '|'
+///         | '{' branch (';' branch)* '}'
+/// branch := expr ':' expr | expr
+/// ```
+pub fn parse(src: &str) -> Result<Expr, Error> {
+    let mut parser = Parser::new(src)?;
+    let expr = parser.expr()?;
+    parser.finish()?;
+
+    Ok(expr)
+}
+
+/// Parses `src` as a list of comma separated expressions, such as the two coordinates of a
+/// parametric curve
+pub fn parse_list(src: &str) -> Result<Vec<Expr>, Error> {
+    let mut parser = Parser::new(src)?;
+
+    let mut exprs = vec![parser.expr()?];
+    while parser.eat(&TokenKind::Comma).is_some() {
+        exprs.push(parser.expr()?);
+    }
+    parser.finish()?;
+
+    Ok(exprs)
+}
+
+/// An identifier read from the source, with its span
+pub type Name = (String, Span);
+
+/// Parses `src` as the definition of a helper function, like `g(x, c) = x^2 - c`. Returns the
+/// name of the function and its parameters, each with its span, and the body of the function.
+///
+/// ```text
+/// definition := ident '(' ident (',' ident)* ')' '=' expr
+/// ```
+pub fn parse_definition(src: &str) -> Result<(Name, Vec<Name>, Expr), Error> {
+    let mut parser = Parser::new(src)?;
+
+    let name = parser.ident()?;
+    let open = parser.expect(&TokenKind::LParen)?;
+    let mut params = vec![parser.ident()?];
+    while parser.eat(&TokenKind::Comma).is_some() {
+        params.push(parser.ident()?);
+    }
+    parser.close_paren(open)?;
+    parser.expect(&TokenKind::Equals)?;
+
+    let body = parser.expr()?;
+    parser.finish()?;
+
+    Ok((name, params, body))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Length of the source, to point at its end
+    len: usize,
+    /// Current recursion depth
+    depth: usize,
+    /// Number of absolute values opened and not closed yet
+    abs: usize,
+}
+
+impl Parser {
+    fn new(src: &str) -> Result<Parser, Error> {
+        let tokens = tokenize(src)?;
+
+        if tokens.is_empty() {
+            return Err(Error::new(Span::new(0, src.len()), ErrorKind::Empty));
+        }
+
+        Ok(Parser {
+            tokens,
+            pos: 0,
+            len: src.len(),
+            depth: 0,
+            abs: 0,
+        })
+    }
+
+    /// Checks that all the tokens have been consumed
+    fn finish(&self) -> Result<(), Error> {
+        match self.peek() {
+            None => Ok(()),
+            Some(t) if t.kind == TokenKind::RParen => {
+                Err(Error::new(t.span, ErrorKind::UnopenedParen))
+            }
+            Some(t) => Err(Error::new(t.span, ErrorKind::UnexpectedToken)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        match self.tokens.get(self.pos) {
+            Some(t) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => Err(Error::new(
+                Span::new(self.len, self.len),
+                ErrorKind::UnexpectedEnd,
+            )),
+        }
+    }
+
+    /// Consumes the next token, which must be `kind`
+    fn expect(&mut self, kind: &TokenKind) -> Result<Span, Error> {
+        let token = self.next()?;
+        if token.kind == *kind {
+            Ok(token.span)
+        } else {
+            Err(Error::new(token.span, ErrorKind::UnexpectedToken))
+        }
+    }
+
+    /// Consumes the next token, which must be an identifier
+    fn ident(&mut self) -> Result<Name, Error> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Ident(name) => Ok((name, token.span)),
+            _ => Err(Error::new(token.span, ErrorKind::UnexpectedToken)),
+        }
+    }
+
+    /// Rest of a piecewise expression opened by the brace at `open`, as a call to `piecewise`
+    fn piecewise(&mut self, open: Span) -> Result<Expr, Error> {
+        let mut args = Vec::new();
+
+        loop {
+            let expr = self.expr()?;
+            let is_default = self.eat(&TokenKind::Colon).is_none();
+            args.push(expr);
+            if !is_default {
+                args.push(self.expr()?);
+            }
+
+            if let Some(close) = self.eat(&TokenKind::RBrace) {
+                return Ok(Expr::new(
+                    ExprKind::Call(Func::Piecewise.name().into(), args),
+                    open.join(close),
+                ));
+            }
+
+            match self.peek() {
+                None => return Err(Error::new(open, ErrorKind::UnclosedBrace)),
+                // The value without condition must come last
+                Some(t) if t.kind != TokenKind::Semicolon || is_default => {
+                    return Err(Error::new(t.span, ErrorKind::UnexpectedToken))
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// Consumes the next token if it is `kind`
+    fn eat(&mut self, kind: &TokenKind) -> Option<Span> {
+        match self.peek() {
+            Some(t) if t.kind == *kind => {
+                let span = t.span;
+                self.pos += 1;
+                Some(span)
+            }
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let lhs = self.sum()?;
+        let op = match self.comparison() {
+            Some(op) => op,
+            None => return Ok(lhs),
+        };
+
+        let rhs = self.sum()?;
+        let next = self.peek().map(|t| t.span);
+        match next {
+            Some(span) if self.comparison().is_some() => {
+                Err(Error::new(span, ErrorKind::UnexpectedToken))
+            }
+            _ => Ok(Expr::binary(op, lhs, rhs)),
+        }
+    }
+
+    /// The comparison operator of the next token, which is consumed, if it is one
+    fn comparison(&mut self) -> Option<BinaryOp> {
+        let op = match self.peek()?.kind {
+            TokenKind::Less => BinaryOp::Lt,
+            TokenKind::LessEqual => BinaryOp::Le,
+            TokenKind::Greater => BinaryOp::Gt,
+            TokenKind::GreaterEqual => BinaryOp::Ge,
+            TokenKind::EqualEqual => BinaryOp::Eq,
+            TokenKind::NotEqual => BinaryOp::Ne,
+            _ => return None,
+        };
+
+        self.pos += 1;
+        Some(op)
+    }
+
+    fn sum(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let expr = self.sum_inner();
+        self.depth = depth;
+
+        expr
+    }
+
+    fn sum_inner(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.term()?;
+
+        loop {
+            let op = if self.eat(&TokenKind::Plus).is_some() {
+                BinaryOp::Add
+            } else if self.eat(&TokenKind::Minus).is_some() {
+                BinaryOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+
+            // The operands read so far go one level deeper, under the new operator
+            self.deeper()?;
+            let rhs = self.term()?;
+            lhs = Expr::binary(op, lhs, rhs);
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let expr = self.term_inner();
+        self.depth = depth;
+
+        expr
+    }
+
+    fn term_inner(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.unary()?;
+
+        loop {
+            let op = if self.eat(&TokenKind::Star).is_some() {
+                BinaryOp::Mul
+            } else if self.eat(&TokenKind::Slash).is_some() {
+                BinaryOp::Div
+            } else if self.eat(&TokenKind::Percent).is_some() {
+                BinaryOp::Rem
+            } else if self.implicit_factor() {
+                BinaryOp::Mul
+            } else {
+                return Ok(lhs);
+            };
+
+            self.deeper()?;
+            let rhs = self.unary()?;
+            lhs = Expr::binary(op, lhs, rhs);
+        }
+    }
+
+    /// True if the next token starts a factor multiplied without `*`. Inside an absolute value a
+    /// `|` closes it rather than starting a new one.
+    fn implicit_factor(&self) -> bool {
+        match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Ident(_) | TokenKind::LParen | TokenKind::LBrace) => true,
+            Some(TokenKind::Pipe) => self.abs == 0,
+            _ => false,
+        }
+    }
+
+    /// Goes one level deeper in the tree, failing if that nests more than `MAX_NESTING`
+    fn deeper(&mut self) -> Result<(), Error> {
+        if self.depth >= MAX_NESTING {
+            let span = self
+                .peek()
+                .map_or(Span::new(self.len, self.len), |t| t.span);
+            return Err(Error::new(span, ErrorKind::TooDeep { max: MAX_NESTING }));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        self.deeper()?;
+        let expr = self.unary_inner();
+        self.depth -= 1;
+
+        expr
+    }
+
+    fn unary_inner(&mut self) -> Result<Expr, Error> {
+        if let Some(span) = self.eat(&TokenKind::Minus) {
+            let expr = self.unary()?;
+            let span = span.join(expr.span);
+            return Ok(Expr::new(
+                ExprKind::Unary(UnaryOp::Neg, Box::new(expr)),
+                span,
+            ));
+        }
+
+        if self.eat(&TokenKind::Plus).is_some() {
+            return self.unary();
+        }
+
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expr, Error> {
+        let base = self.atom()?;
+
+        if self.eat(&TokenKind::Caret).is_some() {
+            let exp = self.unary()?;
+            return Ok(Expr::binary(BinaryOp::Pow, base, exp));
+        }
+
+        if let Some(Token {
+            kind: TokenKind::Superscript(v),
+            span,
+        }) = self.peek().cloned()
+        {
+            self.pos += 1;
+            let number = Expr::new(ExprKind::Number(v.abs()), span);
+            let exp = if v < 0.0 {
+                Expr::new(ExprKind::Unary(UnaryOp::Neg, Box::new(number)), span)
+            } else {
+                number
+            };
+            return Ok(Expr::binary(BinaryOp::Pow, base, exp));
+        }
+
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, Error> {
+        let token = self.next()?;
+
+        match token.kind {
+            TokenKind::Number(v) => Ok(Expr::new(ExprKind::Number(v), token.span)),
+            TokenKind::Ident(name) => match self.eat(&TokenKind::LParen) {
+                Some(open) => {
+                    let mut args = vec![self.expr()?];
+                    while self.eat(&TokenKind::Comma).is_some() {
+                        args.push(self.expr()?);
+                    }
+                    let close = self.close_paren(open)?;
+                    Ok(Expr::new(
+                        ExprKind::Call(name, args),
+                        token.span.join(close),
+                    ))
+                }
+                None => Ok(Expr::new(ExprKind::Var(name), token.span)),
+            },
+            TokenKind::LParen => {
+                let mut expr = self.expr()?;
+                let close = self.close_paren(token.span)?;
+                expr.span = token.span.join(close);
+                Ok(expr)
+            }
+            TokenKind::Pipe => {
+                self.abs += 1;
+                let arg = self.expr();
+                self.abs -= 1;
+
+                let arg = arg?;
+                match self.eat(&TokenKind::Pipe) {
+                    Some(close) => Ok(Expr::new(
+                        ExprKind::Call("abs".into(), vec![arg]),
+                        token.span.join(close),
+                    )),
+                    None => Err(Error::new(token.span, ErrorKind::UnclosedAbs)),
+                }
+            }
+            TokenKind::LBrace => self.piecewise(token.span),
+            TokenKind::RParen => Err(Error::new(token.span, ErrorKind::UnopenedParen)),
+            _ => Err(Error::new(token.span, ErrorKind::UnexpectedToken)),
+        }
+    }
+
+    /// Consumes the `)` matching the `(` at `open`
+    fn close_paren(&mut self, open: Span) -> Result<Span, Error> {
+        match self.peek() {
+            Some(t) if t.kind == TokenKind::RParen => {
+                let span = t.span;
+                self.pos += 1;
+                Ok(span)
+            }
+            Some(t) => Err(Error::new(t.span, ErrorKind::UnexpectedToken)),
+            None => Err(Error::new(open, ErrorKind::UnclosedParen)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(src: &str) -> String {
+        parse(src).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(roundtrip("1+2*3"), "1 + 2 * 3");
+        assert_eq!(roundtrip("(1+2)*3"), "(1 + 2) * 3");
+        assert_eq!(roundtrip("1-(2-3)"), "1 - (2 - 3)");
+        assert_eq!(roundtrip("(1-2)-3"), "1 - 2 - 3");
+        assert_eq!(roundtrip("2^3^4"), "2^3^4");
+        assert_eq!(roundtrip("(2^3)^4"), "(2^3)^4");
+        assert_eq!(roundtrip("-x^2"), "-x^2");
+        assert_eq!(roundtrip("(-x)^2"), "(-x)^2");
+        assert_eq!(roundtrip("x^-2"), "x^(-2)");
+        assert_eq!(roundtrip("-(x*2)"), "-(x * 2)");
+    }
+
+    #[test]
+    fn test_calls() {
+        assert_eq!(roundtrip("max( x ,1,sin(2))"), "max(x, 1, sin(2))");
+    }
+
+    #[test]
+    fn test_implicit_multiplication() {
+        assert_eq!(roundtrip("2x"), "2 * x");
+        assert_eq!(roundtrip("3sin(x)"), "3 * sin(x)");
+        assert_eq!(roundtrip("(x+1)(x-2)"), "(x + 1) * (x - 2)");
+        assert_eq!(roundtrip("-2x^2y"), "-2 * x^2 * y");
+        assert_eq!(roundtrip("1/2x"), "1 / 2 * x");
+        assert_eq!(roundtrip("2e"), "2 * e");
+        assert_eq!(roundtrip("x(x+1)"), "x(x + 1)");
+    }
+
+    #[test]
+    fn test_unicode() {
+        assert_eq!(roundtrip("x²"), "x^2");
+        assert_eq!(roundtrip("2x³ - (x+1)²"), "2 * x^3 - (x + 1)^2");
+        assert_eq!(roundtrip("x⁻¹"), "x^(-1)");
+        assert_eq!(parse("x⁻¹").unwrap(), parse("x^-1").unwrap());
+        assert_eq!(roundtrip("2π"), "2 * pi");
+    }
+
+    #[test]
+    fn test_abs() {
+        assert_eq!(roundtrip("|x - 1|"), "abs(x - 1)");
+        assert_eq!(roundtrip("2|x||y|"), "2 * abs(x) * abs(y)");
+        assert_eq!(roundtrip("||x| - 1|"), "abs(abs(x) - 1)");
+        assert_eq!(roundtrip("|x|²"), "abs(x)^2");
+
+        let e = parse("2 + |x").unwrap_err();
+        assert_eq!((e.span, e.kind), (Span::new(4, 5), ErrorKind::UnclosedAbs));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(roundtrip("x+1<=2*y"), "x + 1 <= 2 * y");
+        assert_eq!(roundtrip("(x<1)==(y>2)"), "(x < 1) == (y > 2)");
+        assert_eq!(roundtrip("-(x!=0)"), "-(x != 0)");
+        assert_eq!(roundtrip("if(x<0,1,2)"), "if(x < 0, 1, 2)");
+
+        let e = parse("0 < x < 1").unwrap_err();
+        assert_eq!(
+            (e.span, e.kind),
+            (Span::new(6, 7), ErrorKind::UnexpectedToken)
+        );
+    }
+
+    #[test]
+    fn test_piecewise() {
+        assert_eq!(
+            roundtrip("{x<0: x^2; x>=0: -x}"),
+            "{x < 0: x^2; x >= 0: -x}"
+        );
+        assert_eq!(roundtrip("2{x<0: 1; 0}"), "2 * {x < 0: 1; 0}");
+        assert_eq!(roundtrip("piecewise(x > 1, 2)"), "{x > 1: 2}");
+        assert_eq!(parse("{x < 0: 1; 0}").unwrap().span, Span::new(0, 13));
+
+        let err = |src| {
+            let e = parse(src).unwrap_err();
+            (e.span.start, e.span.end, e.kind)
+        };
+        assert_eq!(err("{x < 0: 1"), (0, 1, ErrorKind::UnclosedBrace));
+        assert_eq!(err("{0; x < 0: 1}"), (2, 3, ErrorKind::UnexpectedToken));
+        assert_eq!(err("{x < 0: 1, 2}"), (9, 10, ErrorKind::UnexpectedToken));
+    }
+
+    #[test]
+    fn test_spans() {
+        let expr = parse("2 * (x + 1)").unwrap();
+        assert_eq!(expr.span, Span::new(0, 11));
+
+        match expr.kind {
+            ExprKind::Binary(BinaryOp::Mul, _, rhs) => assert_eq!(rhs.span, Span::new(4, 11)),
+            _ => panic!("Expected a product"),
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |src| {
+            let e = parse(src).unwrap_err();
+            (e.span.start, e.span.end, e.kind)
+        };
+
+        assert_eq!(err(""), (0, 0, ErrorKind::Empty));
+        assert_eq!(err("x +"), (3, 3, ErrorKind::UnexpectedEnd));
+        assert_eq!(err("(x + 1))"), (7, 8, ErrorKind::UnopenedParen));
+        assert_eq!(err("sin((x)"), (3, 4, ErrorKind::UnclosedParen));
+        assert_eq!(err("x 2"), (2, 3, ErrorKind::UnexpectedToken));
+        assert_eq!(err("x, 2"), (1, 2, ErrorKind::UnexpectedToken));
+        assert_eq!(
+            parse(&"(".repeat(10_000)).unwrap_err().kind,
+            ErrorKind::TooDeep { max: MAX_NESTING }
+        );
+        assert_eq!(
+            parse(&"x + 2x".repeat(10_000)).unwrap_err().kind,
+            ErrorKind::TooDeep { max: MAX_NESTING }
+        );
+    }
+
+    #[test]
+    fn test_definition() {
+        let (name, params, body) = parse_definition("bump(x, c) = exp(-(x - c)^2)").unwrap();
+        assert_eq!(name, ("bump".into(), Span::new(0, 4)));
+        assert_eq!(
+            params,
+            vec![("x".into(), Span::new(5, 6)), ("c".into(), Span::new(8, 9))]
+        );
+        assert_eq!(body.to_string(), "exp(-(x - c)^2)");
+
+        let err = |src| parse_definition(src).unwrap_err().span;
+        assert_eq!(err("g(x) x"), Span::new(5, 6));
+        assert_eq!(err("g(2) = x"), Span::new(2, 3));
+        assert_eq!(err("g = x"), Span::new(2, 3));
+        assert_eq!(err("g(x) = x = 2"), Span::new(9, 10));
+    }
+
+    #[test]
+    fn test_list() {
+        let exprs = parse_list("cos(t), max(t, 1)").unwrap();
+        let texts: Vec<_> = exprs.iter().map(|e| e.to_string()).collect();
+
+        assert_eq!(texts, vec!["cos(t)", "max(t, 1)"]);
+        assert_eq!(exprs[1].span, Span::new(8, 17));
+    }
+}
@@ -0,0 +1,402 @@
+use wasm_bindgen::prelude::*;
+
+use super::ast::{exponent_form, PREC_ATOM, PREC_MUL, PREC_NEG, PREC_POW};
+use super::latex::{FUNCTIONS, GREEK};
+use super::{BinaryOp, Expr, ExprKind, Func, UnaryOp};
+
+/// Notation in which a formula is written out
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notation {
+    /// The plain formula syntax, as stored once shot
+    Text,
+    Latex,
+    /// Presentation MathML, a whole `<math>` element
+    MathMl,
+}
+
+/// Greek letters in the order of [`GREEK`]
+const GREEK_LETTERS: &str = "αβγδεζηθικλμνξρστφχψω";
+
+/// LaTeX source of `expr` with only the necessary parentheses, like `\sin(x) \cdot e^{-x}`.
+/// Functions with a command of their own use it and the others are written with
+/// `\operatorname`. Piecewise expressions become a `cases` environment.
+pub fn to_latex(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Number(v) => latex_number(*v),
+        ExprKind::Var(name) => latex_var(name),
+        ExprKind::Unary(UnaryOp::Neg, e) => format!("-{}", latex_operand(e, PREC_NEG)),
+        ExprKind::Binary(BinaryOp::Div, l, r) => {
+            format!(r"\frac{{{}}}{{{}}}", to_latex(l), to_latex(r))
+        }
+        ExprKind::Binary(BinaryOp::Pow, l, r) => {
+            format!("{}^{{{}}}", latex_operand(l, PREC_ATOM), to_latex(r))
+        }
+        ExprKind::Binary(op, l, r) => {
+            let (l_prec, r_prec) = op.operand_precedence();
+            let (lhs, rhs) = (latex_operand(l, l_prec), latex_operand(r, r_prec));
+
+            if *op == BinaryOp::Mul && juxtaposes(l, r) {
+                format!("{}{}", lhs, rhs)
+            } else {
+                format!("{} {} {}", lhs, latex_symbol(*op), rhs)
+            }
+        }
+        ExprKind::Call(name, args) => latex_call(name, args),
+    }
+}
+
+/// Presentation MathML of `expr` with only the necessary parentheses, as a single element to be
+/// placed in a `<math>` element
+pub fn to_mathml(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Number(v) if v.is_sign_negative() && *v != 0.0 => {
+            format!("<mrow><mo>−</mo>{}</mrow>", mathml_number(-v))
+        }
+        ExprKind::Number(v) => mathml_number(*v),
+        ExprKind::Var(name) => mathml_var(name),
+        ExprKind::Unary(UnaryOp::Neg, e) => {
+            format!("<mrow><mo>−</mo>{}</mrow>", mathml_operand(e, PREC_NEG))
+        }
+        ExprKind::Binary(BinaryOp::Div, l, r) => {
+            format!("<mfrac>{}{}</mfrac>", to_mathml(l), to_mathml(r))
+        }
+        ExprKind::Binary(BinaryOp::Pow, l, r) => {
+            format!(
+                "<msup>{}{}</msup>",
+                mathml_operand(l, PREC_ATOM),
+                to_mathml(r)
+            )
+        }
+        ExprKind::Binary(op, l, r) => {
+            let (l_prec, r_prec) = op.operand_precedence();
+            let symbol = if *op == BinaryOp::Mul && juxtaposes(l, r) {
+                // Invisible times
+                "&#x2062;"
+            } else {
+                mathml_symbol(*op)
+            };
+
+            format!(
+                "<mrow>{}<mo>{}</mo>{}</mrow>",
+                mathml_operand(l, l_prec),
+                symbol,
+                mathml_operand(r, r_prec)
+            )
+        }
+        ExprKind::Call(name, args) => mathml_call(name, args),
+    }
+}
+
+/// Binding strength of `expr` once written out. A fraction is drawn as a block, so it never
+/// needs parentheses except as the base of a power, while a number in exponent form is a product.
+fn precedence(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::Binary(BinaryOp::Div, _, _) => PREC_POW,
+        ExprKind::Number(v) if exponent_form(*v).is_some() => expr.precedence().min(PREC_MUL),
+        _ => expr.precedence(),
+    }
+}
+
+/// True if the product `l * r` can be written without an operator, like `2x` or `3\sin(x)`
+fn juxtaposes(l: &Expr, r: &Expr) -> bool {
+    let r = match &r.kind {
+        ExprKind::Binary(BinaryOp::Pow, base, _) => base,
+        _ => r,
+    };
+
+    matches!(l.kind, ExprKind::Number(v) if v.is_sign_positive())
+        && match &r.kind {
+            ExprKind::Var(_) => true,
+            ExprKind::Call(name, _) => name != Func::Piecewise.name(),
+            _ => false,
+        }
+}
+
+/// Command of the function `name` if it has one
+fn command(name: &str) -> Option<&'static str> {
+    FUNCTIONS
+        .iter()
+        .find(|(_, f)| f.name() == name)
+        .map(|(c, _)| *c)
+}
+
+/// `name` without the `d` of a derivative, like `y` for `dy`
+fn derivative_of(name: &str) -> Option<&str> {
+    name.strip_prefix('d')
+        .filter(|v| v.chars().count() == 1 || GREEK.contains(v))
+}
+
+/// LaTeX of `v`, like `2.5 \times 10^{-12}` when it is too large or too small to be written in
+/// full
+fn latex_number(v: f64) -> String {
+    match exponent_form(v) {
+        Some((mantissa, exponent)) => format!(r"{} \times 10^{{{}}}", mantissa, exponent),
+        None => v.to_string(),
+    }
+}
+
+fn latex_operand(expr: &Expr, min_prec: u8) -> String {
+    let text = to_latex(expr);
+    if precedence(expr) >= min_prec {
+        text
+    } else if text.contains(r"\frac") || text.contains(r"\begin") {
+        format!(r"\left({}\right)", text)
+    } else {
+        format!("({})", text)
+    }
+}
+
+fn latex_var(name: &str) -> String {
+    if name == "pi" || GREEK.contains(&name) {
+        return format!(r"\{}", name);
+    }
+    if let Some(v) = derivative_of(name) {
+        return format!("{}'", latex_var(v));
+    }
+    if let Some((base, sub)) = name.split_once('_') {
+        return format!("{}_{{{}}}", latex_var(base), sub);
+    }
+
+    if name.chars().count() == 1 {
+        name.into()
+    } else {
+        format!(r"\mathit{{{}}}", name)
+    }
+}
+
+fn latex_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Mul => r"\cdot",
+        BinaryOp::Rem => r"\bmod",
+        BinaryOp::Le => r"\le",
+        BinaryOp::Ge => r"\ge",
+        BinaryOp::Eq => "=",
+        BinaryOp::Ne => r"\ne",
+        _ => op.symbol(),
+    }
+}
+
+fn latex_call(name: &str, args: &[Expr]) -> String {
+    let texts: Vec<String> = args.iter().map(to_latex).collect();
+
+    match (Func::from_name(name), texts.as_slice()) {
+        (Some(Func::Sqrt), [arg]) => format!(r"\sqrt{{{}}}", arg),
+        (Some(Func::Abs), [arg]) => format!(r"\left|{}\right|", arg),
+        (Some(Func::Floor), [arg]) => format!(r"\left\lfloor {}\right\rfloor", arg),
+        (Some(Func::Ceil), [arg]) => format!(r"\left\lceil {}\right\rceil", arg),
+        (Some(Func::Piecewise), [_, _, ..]) => {
+            let rows: Vec<String> = texts
+                .chunks(2)
+                .map(|branch| match branch {
+                    [cond, value] => format!(r"{} & \text{{if }} {}", value, cond),
+                    _ => format!(r"{} & \text{{otherwise}}", branch[0]),
+                })
+                .collect();
+            format!(r"\begin{{cases}} {} \end{{cases}}", rows.join(r" \\ "))
+        }
+        _ => {
+            let head = match command(name) {
+                Some(c) => format!(r"\{}", c),
+                None => format!(r"\operatorname{{{}}}", name),
+            };
+            let args = texts.join(", ");
+            if args.contains(r"\frac") || args.contains(r"\begin") {
+                format!(r"{}\left({}\right)", head, args)
+            } else {
+                format!("{}({})", head, args)
+            }
+        }
+    }
+}
+
+/// MathML of `v`, like `2.5 × 10⁻¹²` when it is too large or too small to be written in full
+fn mathml_number(v: f64) -> String {
+    match exponent_form(v) {
+        Some((mantissa, exponent)) => format!(
+            "<mrow><mn>{}</mn><mo>×</mo><msup><mn>10</mn><mn>{}</mn></msup></mrow>",
+            mantissa, exponent
+        ),
+        None => format!("<mn>{}</mn>", v),
+    }
+}
+
+fn mathml_operand(expr: &Expr, min_prec: u8) -> String {
+    let markup = to_mathml(expr);
+    if precedence(expr) >= min_prec {
+        markup
+    } else {
+        format!("<mrow><mo>(</mo>{}<mo>)</mo></mrow>", markup)
+    }
+}
+
+fn mathml_var(name: &str) -> String {
+    if name == "pi" {
+        return "<mi>π</mi>".into();
+    }
+    if let Some(i) = GREEK.iter().position(|g| *g == name) {
+        return format!("<mi>{}</mi>", GREEK_LETTERS.chars().nth(i).unwrap());
+    }
+    if let Some(v) = derivative_of(name) {
+        return format!("<msup>{}<mo>′</mo></msup>", mathml_var(v));
+    }
+    if let Some((base, sub)) = name.split_once('_') {
+        let sub = if sub.chars().all(|c| c.is_ascii_digit()) {
+            format!("<mn>{}</mn>", sub)
+        } else {
+            format!("<mi>{}</mi>", sub)
+        };
+        return format!("<msub>{}{}</msub>", mathml_var(base), sub);
+    }
+
+    format!("<mi>{}</mi>", name)
+}
+
+fn mathml_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Sub => "−",
+        BinaryOp::Mul => "⋅",
+        BinaryOp::Rem => "mod",
+        BinaryOp::Lt => "&lt;",
+        BinaryOp::Le => "≤",
+        BinaryOp::Gt => "&gt;",
+        BinaryOp::Ge => "≥",
+        BinaryOp::Eq => "=",
+        BinaryOp::Ne => "≠",
+        _ => op.symbol(),
+    }
+}
+
+fn mathml_call(name: &str, args: &[Expr]) -> String {
+    let markups: Vec<String> = args.iter().map(to_mathml).collect();
+    let fenced = |open: &str, arg: &str, close: &str| {
+        format!("<mrow><mo>{}</mo>{}<mo>{}</mo></mrow>", open, arg, close)
+    };
+
+    match (Func::from_name(name), markups.as_slice()) {
+        (Some(Func::Sqrt), [arg]) => format!("<msqrt>{}</msqrt>", arg),
+        (Some(Func::Abs), [arg]) => fenced("|", arg, "|"),
+        (Some(Func::Floor), [arg]) => fenced("⌊", arg, "⌋"),
+        (Some(Func::Ceil), [arg]) => fenced("⌈", arg, "⌉"),
+        (Some(Func::Piecewise), [_, _, ..]) => {
+            let rows: String = markups
+                .chunks(2)
+                .map(|branch| match branch {
+                    [cond, value] => format!(
+                        "<mtr><mtd>{}</mtd><mtd><mtext>if </mtext>{}</mtd></mtr>",
+                        value, cond
+                    ),
+                    _ => format!(
+                        "<mtr><mtd>{}</mtd><mtd><mtext>otherwise</mtext></mtd></mtr>",
+                        branch[0]
+                    ),
+                })
+                .collect();
+            format!("<mrow><mo>{{</mo><mtable>{}</mtable></mrow>", rows)
+        }
+        _ => {
+            let head = command(name).unwrap_or(name);
+            // Function application
+            format!(
+                "<mrow><mi>{}</mi><mo>&#x2061;</mo>{}</mrow>",
+                head,
+                fenced("(", &markups.join("<mo>,</mo>"), ")")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::{parse, parse_latex};
+
+    fn latex(src: &str) -> String {
+        to_latex(&parse(src).unwrap())
+    }
+
+    fn mathml(src: &str) -> String {
+        to_mathml(&parse(src).unwrap())
+    }
+
+    #[test]
+    fn test_latex() {
+        assert_eq!(latex("sin(x) * e^(-x)"), r"\sin(x) \cdot e^{-x}");
+        assert_eq!(latex("(x + 1) / (x - 1)"), r"\frac{x + 1}{x - 1}");
+        assert_eq!(latex("(x / 2)^2"), r"\left(\frac{x}{2}\right)^{2}");
+        assert_eq!(latex("2 * (x / 3)"), r"2 \cdot \frac{x}{3}");
+        assert_eq!(latex("2x² + 3sin(πx)"), r"2x^{2} + 3\sin(\pi \cdot x)");
+        assert_eq!(latex("(-x)^2 - (x - 1)"), r"(-x)^{2} - (x - 1)");
+        assert_eq!(latex("x % 3 <= theta"), r"x \bmod 3 \le \theta");
+        assert_eq!(latex("dy + x_1"), r"y' + x_{1}");
+        assert_eq!(
+            latex("sqrt(|x|) + floor(x) + atan2(x, 1)"),
+            r"\sqrt{\left|x\right|} + \left\lfloor x\right\rfloor + \operatorname{atan2}(x, 1)"
+        );
+        assert_eq!(
+            latex("2.5e-12 + 1e300"),
+            r"2.5 \times 10^{-12} + 1 \times 10^{300}"
+        );
+        assert_eq!(latex("(3e20)^x"), r"(3 \times 10^{20})^{x}");
+        assert_eq!(
+            latex("{x < 0: -x; x}"),
+            r"\begin{cases} -x & \text{if } x < 0 \\ x & \text{otherwise} \end{cases}"
+        );
+    }
+
+    #[test]
+    fn test_latex_reads_back() {
+        let formulas = [
+            "sin(x) * e^(-x)",
+            "(x + 1) / (x - 1) - 2 / x",
+            "-(x / 2)^3 + 2x² * cos(x)^2",
+            "x - (x - 1) - (2 - x^2)",
+            "abs(x - 1) + floor(x / 2) + ceil(x) + sqrt(x + 1)",
+            "asin(x) + max(x, 1) + bump(x, 2)",
+            "2^3^x + (2^3)^x",
+            "2pi * theta + x_1 * dy",
+            "x <= 2 * pi",
+        ];
+
+        for src in formulas {
+            let expr = parse(src).unwrap();
+            let latex = to_latex(&expr);
+            assert_eq!(parse_latex(&latex).unwrap(), vec![expr], "{}", latex);
+        }
+    }
+
+    #[test]
+    fn test_mathml() {
+        assert_eq!(
+            mathml("sin(x) * e^(-x)"),
+            "<mrow><mrow><mi>sin</mi><mo>&#x2061;</mo><mrow><mo>(</mo><mi>x</mi><mo>)</mo></mrow>\
+             </mrow><mo>⋅</mo><msup><mi>e</mi><mrow><mo>−</mo><mi>x</mi></mrow></msup></mrow>"
+        );
+        assert_eq!(
+            mathml("(x + 1) / 2x"),
+            "<mrow><mfrac><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mn>2</mn></mfrac>\
+             <mo>⋅</mo><mi>x</mi></mrow>"
+        );
+        assert_eq!(
+            mathml("2(x - 1) < dy"),
+            "<mrow><mrow><mn>2</mn><mo>⋅</mo><mrow><mo>(</mo><mrow><mi>x</mi><mo>−</mo>\
+             <mn>1</mn></mrow><mo>)</mo></mrow></mrow><mo>&lt;</mo><msup><mi>y</mi><mo>′</mo>\
+             </msup></mrow>"
+        );
+        assert_eq!(
+            mathml("3theta²"),
+            "<mrow><mn>3</mn><mo>&#x2062;</mo><msup><mi>θ</mi><mn>2</mn></msup></mrow>"
+        );
+        assert_eq!(
+            mathml("2.5e-12 * x"),
+            "<mrow><mrow><mn>2.5</mn><mo>×</mo><msup><mn>10</mn><mn>-12</mn></msup></mrow>\
+             <mo>&#x2062;</mo><mi>x</mi></mrow>"
+        );
+        assert_eq!(
+            mathml("{x < 0: 1; 2}"),
+            "<mrow><mo>{</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mtext>if </mtext><mrow>\
+             <mi>x</mi><mo>&lt;</mo><mn>0</mn></mrow></mtd></mtr><mtr><mtd><mn>2</mn></mtd><mtd>\
+             <mtext>otherwise</mtext></mtd></mtr></mtable></mrow>"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use super::ast::PREC_NEG;
+use super::builtin::Func;
+use super::{AngleUnit, BinaryOp, Expr, ExprKind, UnaryOp};
+
+/// Rewrites `expr` into an equivalent and usually shorter expression, folding constant
+/// sub-expressions and removing neutral elements like `0 + x` or `1 * x`. Named constants such
+/// as `pi` are kept as they are.
+pub fn simplify(expr: Expr) -> Expr {
+    simplify_in(expr, AngleUnit::Radians)
+}
+
+/// [`simplify`] for an expression whose angles are in `unit`, which matters when folding calls
+/// to trigonometric functions
+pub fn simplify_in(expr: Expr, unit: AngleUnit) -> Expr {
+    let span = expr.span;
+    let mut simplified = match expr.kind {
+        ExprKind::Number(_) | ExprKind::Var(_) => return expr,
+        ExprKind::Unary(UnaryOp::Neg, e) => neg(simplify_in(*e, unit)),
+        ExprKind::Binary(op, l, r) => binary(op, simplify_in(*l, unit), simplify_in(*r, unit)),
+        ExprKind::Call(name, args) => call(
+            name,
+            args.into_iter().map(|a| simplify_in(a, unit)).collect(),
+            unit,
+        ),
+    };
+
+    simplified.span = span;
+    simplified
+}
+
+fn value(expr: &Expr) -> Option<f64> {
+    match expr.kind {
+        ExprKind::Number(v) => Some(v),
+        _ => None,
+    }
+}
+
+fn is(expr: &Expr, v: f64) -> bool {
+    value(expr) == Some(v)
+}
+
+/// A number node for `v`, if it can be written back as a formula
+fn fold(v: f64) -> Option<Expr> {
+    v.is_finite().then(|| Expr::number(v))
+}
+
+fn neg(e: Expr) -> Expr {
+    match e.kind {
+        ExprKind::Number(v) => Expr::number(-v),
+        ExprKind::Unary(UnaryOp::Neg, inner) => *inner,
+        // -(-a * b) is a * b
+        ExprKind::Binary(op @ (BinaryOp::Mul | BinaryOp::Div), l, r)
+            if l.precedence() == PREC_NEG =>
+        {
+            binary(op, neg(*l), *r)
+        }
+        kind => Expr::unary(UnaryOp::Neg, Expr::new(kind, e.span)),
+    }
+}
+
+fn binary(op: BinaryOp, l: Expr, r: Expr) -> Expr {
+    if let (Some(a), Some(b)) = (value(&l), value(&r)) {
+        if let Some(e) = fold(op.apply(a, b)) {
+            return e;
+        }
+    }
+
+    match op {
+        BinaryOp::Add if is(&l, 0.0) => r,
+        BinaryOp::Add | BinaryOp::Sub if is(&r, 0.0) => l,
+        BinaryOp::Sub if is(&l, 0.0) => neg(r),
+        BinaryOp::Add | BinaryOp::Sub => match r.kind {
+            // x + -y is x - y and x - -y is x + y
+            ExprKind::Unary(UnaryOp::Neg, inner) => {
+                let op = if op == BinaryOp::Add {
+                    BinaryOp::Sub
+                } else {
+                    BinaryOp::Add
+                };
+                binary(op, l, *inner)
+            }
+            ExprKind::Number(v) if v < 0.0 => {
+                let op = if op == BinaryOp::Add {
+                    BinaryOp::Sub
+                } else {
+                    BinaryOp::Add
+                };
+                Expr::binary(op, l, Expr::number(-v))
+            }
+            kind => Expr::binary(op, l, Expr::new(kind, r.span)),
+        },
+        BinaryOp::Mul if is(&l, 0.0) || is(&r, 0.0) => Expr::number(0.0),
+        BinaryOp::Mul if is(&l, 1.0) => r,
+        BinaryOp::Mul | BinaryOp::Div if is(&r, 1.0) => l,
+        BinaryOp::Mul if is(&l, -1.0) => neg(r),
+        BinaryOp::Mul | BinaryOp::Div if is(&r, -1.0) => neg(l),
+        BinaryOp::Div if is(&l, 0.0) => Expr::number(0.0),
+        BinaryOp::Pow if is(&r, 0.0) || is(&l, 1.0) => Expr::number(1.0),
+        BinaryOp::Pow if is(&r, 1.0) => l,
+        _ => Expr::binary(op, l, r),
+    }
+}
+
+fn call(name: String, args: Vec<Expr>, unit: AngleUnit) -> Expr {
+    let values: Option<Vec<f64>> = args.iter().map(value).collect();
+    let folded = Func::from_name(&name)
+        .zip(values)
+        .filter(|(f, values)| f.arity().accepts(values.len()))
+        .and_then(|(f, values)| fold(unit.apply(f, &values)));
+
+    if let Some(e) = folded {
+        return e;
+    }
+
+    // A condition whose branches all have the same value does not matter
+    if let Some(Func::If | Func::Piecewise) = Func::from_name(&name) {
+        let mut values = args.iter().skip(1).step_by(2).chain(args.last());
+        let first = values.next().filter(|_| args.len() % 2 == 1).cloned();
+        if let Some(first) = first.filter(|f| values.all(|v| v == f)) {
+            return first;
+        }
+    }
+
+    Expr::call(&name, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::parse;
+
+    fn simplified(src: &str) -> String {
+        simplify(parse(src).unwrap()).to_string()
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(simplified("2 * 3 + x"), "6 + x");
+        assert_eq!(simplified("sin(0) + cos(0) * x"), "x");
+        assert_eq!(simplified("1 / 0 + x"), "1 / 0 + x");
+        assert_eq!(simplified("pi * 2"), "pi * 2");
+    }
+
+    #[test]
+    fn test_identities() {
+        assert_eq!(simplified("0 + x * 1 - 0"), "x");
+        assert_eq!(simplified("0 * sin(x) + x^1"), "x");
+        assert_eq!(simplified("x^0 + 0 - x"), "1 - x");
+        assert_eq!(simplified("x - -y"), "x + y");
+        assert_eq!(simplified("x + -2"), "x - 2");
+        assert_eq!(simplified("--x / -1"), "-x");
+        assert_eq!(simplified("if(x < 0, x, x + 0)"), "x");
+        assert_eq!(simplified("{x < 0: 1; x < 1: 1; 1}"), "1");
+        assert_eq!(simplified("{x < 0: 1; x < 1: 1}"), "{x < 0: 1; x < 1: 1}");
+    }
+}
@@ -1,29 +1,28 @@
 mod arena;
+mod options;
+mod path;
 mod player;
+mod shot;
 mod team;
 
+use std::cell::Cell;
+
 use rand::{rngs::SmallRng, SeedableRng};
 use wasm_bindgen::prelude::*;
 
+use crate::formula::{
+    self, AngleUnit, Diagnostics, Helpers, Notation, Program, SyntaxTokens,
+};
 use crate::utils;
 
 pub use self::arena::Arena;
+pub use self::options::{FormulaMode, NonFinitePolicy, Options, Rotation};
 pub use self::player::Player;
+pub use self::shot::{Impact, Kill, ShotError, ShotResult};
 pub use self::team::Team;
 
 const MAX_ITERS: usize = 100;
 
-#[wasm_bindgen]
-#[derive(Clone)]
-pub struct Options {
-    num_obstacles: usize,
-    min_obstacle_size: f64,
-    max_obstacle_size: f64,
-    players_per_team: Vec<usize>,
-    player_radius: f64,
-    seed: u64,
-}
-
 #[wasm_bindgen]
 #[derive(Clone)]
 /// Represents a game instance.
@@ -43,6 +42,7 @@ impl Game {
     /// inferred by the length of `players_per_team`. The `seed` is used to generate the initial
     /// positions of the obstacles and players.
     #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         x_max: f64,
         y_max: f64,
@@ -53,36 +53,33 @@ impl Game {
         player_radius: f64,
         seed: u64,
     ) -> Result<Game, String> {
-        utils::set_panic_hook();
+        let ops = Options::new(
+            num_obstacles,
+            min_obstacle_size,
+            max_obstacle_size,
+            players_per_team,
+            player_radius,
+            seed,
+        );
+
+        Game::with_options(x_max, y_max, ops)
+    }
 
-        if players_per_team.len() < 2 {
-            return Err("There must be at least two teams".into());
-        }
+    /// Creates a new game instance with an arena that goes from -`x_max` to `x_max` and -`y_max`
+    /// to `y_max`, configured by `ops`.
+    #[wasm_bindgen(js_name = "withOptions")]
+    pub fn with_options(x_max: f64, y_max: f64, ops: Options) -> Result<Game, String> {
+        utils::set_panic_hook();
 
         if x_max <= 0. || y_max <= 0. {
             return Err("x_max and y_max must have a positive value".into());
         }
 
-        if min_obstacle_size <= 0. || player_radius <= 0. {
-            return Err("Obstacle size and player radius must be a positive value".into());
-        }
-
-        if min_obstacle_size > max_obstacle_size {
-            return Err(
-                "The maximum obstacle size must be at least the minimum obstacle size".into(),
-            );
-        }
+        ops.validate()?;
 
         let mut game = Game {
             arena: Arena::new(x_max, y_max),
-            ops: Options {
-                num_obstacles,
-                min_obstacle_size,
-                max_obstacle_size,
-                players_per_team: players_per_team.iter().cloned().collect(),
-                player_radius,
-                seed,
-            },
+            ops,
             current_team: 0,
         };
 
@@ -105,9 +102,157 @@ impl Game {
         self.current_team
     }
 
+    /// Canonical text of `formula`, as it is stored once shot. See [`formula::normalize`].
+    pub fn normalize_formula(&self, formula: &str) -> Result<String, formula::Error> {
+        self.current_limits().check_source(formula)?;
+        formula::normalize(formula)
+    }
+
+    /// `formula` written out in `notation` to display it. See [`formula::render`].
+    pub fn render_formula(
+        &self,
+        formula: &str,
+        notation: Notation,
+    ) -> Result<String, formula::Error> {
+        self.current_limits().check_source(formula)?;
+        formula::render(formula, notation)
+    }
+
     pub fn get_current_formula(&self) -> String {
         self.get_current_player().formula().into()
     }
+
+    /// Sets the initial slope of the next shot of the current player, used by the
+    /// [`FormulaMode::SecondOrderOde`] mode.
+    pub fn set_current_slope(&mut self, slope: f64) -> Result<(), String> {
+        if !slope.is_finite() {
+            return Err("The slope must be a finite number".into());
+        }
+
+        self.get_current_player_mut().set_slope(slope);
+        Ok(())
+    }
+
+    /// Checks `formula` as if the current player was going to shoot it, without changing the
+    /// game.
+    pub fn validate_formula(&self, formula: &str) -> Diagnostics {
+        let mode = self.ops.formula_mode();
+        let player = self.get_current_player();
+        let pos = player.shape().pos();
+
+        let at = match mode {
+            FormulaMode::Parametric | FormulaMode::Polar => vec![0.0],
+            _ => vec![pos.x, pos.y, player.slope()],
+        };
+        let limits = self.current_limits();
+        let ctx = self.formula_context(&limits);
+
+        formula::validate(formula, &at[..ctx.vars.len()], &ctx)
+    }
+
+    /// Complexity score of `formula` for the current player, whose helpers are expanded. See
+    /// [`formula::complexity`].
+    pub fn formula_complexity(&self, formula: &str) -> Result<usize, formula::Error> {
+        let limits = self.current_limits();
+        formula::measure(formula, &self.formula_context(&limits))
+    }
+
+    /// Highest complexity score the formulas of the current player can have, including the
+    /// handicap of their team
+    pub fn get_current_max_complexity(&self) -> usize {
+        self.current_limits().max_complexity()
+    }
+
+    /// Tokens of `formula` for syntax highlighting, counting the helpers of the current player as
+    /// functions. See [`formula::highlight`].
+    pub fn tokenize_formula(&self, formula: &str) -> SyntaxTokens {
+        formula::highlight(formula, self.get_current_player().helpers())
+    }
+
+    /// Helper functions defined by the current player
+    pub fn get_current_helpers(&self) -> Helpers {
+        self.get_current_player().helpers().clone()
+    }
+
+    /// Defines a helper function for the current player, like `g(x) = x^2 - 3`, that the
+    /// formulas of the player can call in later shots. Defining a helper that already exists
+    /// replaces it. Helpers obey the limits of the game once expanded and cannot call themselves.
+    pub fn define_helper(&mut self, definition: &str) -> Result<(), formula::Error> {
+        let limits = self.current_limits();
+        self.get_current_player_mut()
+            .helpers_mut()
+            .define(definition, &limits)
+    }
+
+    /// Removes the helper called `name` of the current player, returning false if there was none
+    pub fn remove_helper(&mut self, name: &str) -> bool {
+        self.get_current_player_mut().helpers_mut().remove(name)
+    }
+
+    /// Derivative of `formula` with respect to the first variable of the [`FormulaMode`] of the
+    /// game, like `x` or `t`, simplified and printed as a formula. The derivative of each
+    /// component is given for the modes that take several of them. Other variables, like `y` in
+    /// the differential equation modes, are taken as constants. The angles of the derivative are
+    /// in the unit of the game.
+    pub fn derivative(&self, formula: &str) -> Result<String, formula::Error> {
+        let limits = self.current_limits();
+        let ctx = self.formula_context(&limits);
+        // Read in radians so that the formula is kept as written, the derivative handles the unit
+        let fs = formula::parse_functions(
+            formula,
+            &formula::Context {
+                unit: AngleUnit::Radians,
+                ..ctx
+            },
+        )?;
+
+        let derivatives: Vec<String> = fs
+            .iter()
+            .map(|f| formula::derivative(f.expr(), ctx.vars[0], ctx.unit).to_string())
+            .collect();
+
+        Ok(derivatives.join(", "))
+    }
+
+    /// Beginning of the shot of `formula` by the current player, without changing the game. The
+    /// preview follows the same path as the shot, cut at the preview length or fraction of the
+    /// game, whichever is shorter, and does not tell which players would be killed.
+    pub fn preview(&self, formula: &str) -> Result<ShotResult, ShotError> {
+        if !self.ops.allow_preview() {
+            return Err(ShotError::PreviewDisabled);
+        }
+
+        let result = self.trace(formula)?;
+        let length = self
+            .ops
+            .preview_length()
+            .min(self.ops.preview_fraction() * result.length());
+
+        Ok(result.truncate(length))
+    }
+
+    /// Shoots `formula` from the current player. The formula is read according to the
+    /// [`FormulaMode`] of the game and the resulting curve always starts at the shooter. Teams on
+    /// the right half of the arena shoot towards negative x. Every enemy player crossed by the
+    /// curve before it hits an obstacle, leaves the arena or breaks off dies, and the turn moves
+    /// on to the next team. Formulas breaking the limits of the game are rejected without
+    /// changing it. Formulas containing a backslash are read as LaTeX, see
+    /// [`formula::parse_latex`]. The shooter keeps the canonical text of the formula.
+    pub fn shoot(&mut self, formula: &str) -> Result<ShotResult, ShotError> {
+        let result = self.trace(formula)?;
+
+        let canonical = formula::normalize(formula)?;
+        self.get_current_player_mut().set_formula(canonical);
+
+        for kill in result.killed() {
+            self.arena.get_teams_mut()[kill.team].kill_player(kill.player);
+        }
+
+        self.arena.get_teams_mut()[self.current_team].next_player();
+        self.next_team();
+
+        Ok(result)
+    }
 }
 
 /// Methods available only in the rust part of the code.
@@ -116,26 +261,27 @@ impl Game {
     // let p_range_y = Range::new(self.arena.bottom(), self.arena.top());
 
     pub fn init(&mut self) -> Result<(), String> {
-        let mut rng: SmallRng = SeedableRng::seed_from_u64(self.ops.seed);
+        let mut rng: SmallRng = SeedableRng::seed_from_u64(self.ops.seed());
 
         for _ in 1..MAX_ITERS {
             // Clear previous data first, just in case
             self.arena.clear();
 
             match self.arena.add_obstacles(
-                self.ops.num_obstacles,
-                self.ops.min_obstacle_size,
-                self.ops.max_obstacle_size,
+                self.ops.num_obstacles(),
+                self.ops.min_obstacle_size(),
+                self.ops.max_obstacle_size(),
                 &mut rng,
             ) {
                 Ok(_) => {}
                 Err(_) => continue,
             };
 
-            match self
-                .arena
-                .add_teams(&self.ops.players_per_team, self.ops.player_radius, &mut rng)
-            {
+            match self.arena.add_teams(
+                self.ops.players_per_team(),
+                self.ops.player_radius(),
+                &mut rng,
+            ) {
                 Ok(_) => return Ok(()),
                 Err(_) => continue,
             };
@@ -144,18 +290,92 @@ impl Game {
         Err("Could not find a valid initial configuration".into())
     }
 
-    pub fn shoot(&mut self, formula: &str) -> Result<(), JsValue> {
-        // Check if formula is valid
+    /// Computes the shot of `formula` by the current player, without changing the game
+    fn trace(&self, formula: &str) -> Result<ShotResult, ShotError> {
+        let mode = self.ops.formula_mode();
+        let limits = &self.current_limits();
+        let fs: Vec<Program> = formula::parse_functions(formula, &self.formula_context(limits))?
+            .iter()
+            .map(Program::compile)
+            .collect();
+
+        // Once the budget of evaluation steps is spent the formula evaluates to NaN, which stops
+        // the shot right away
+        let steps = Cell::new(0);
+        let eval = |f: &Program, args: &[f64]| {
+            steps.set(steps.get() + f.len());
+            if steps.get() > limits.max_steps() {
+                f64::NAN
+            } else {
+                f.eval(args)
+            }
+        };
+        let f = &fs[0];
+
+        let player = self.get_current_player();
+        let origin = player.shape().pos();
+        let direction = self.arena.get_teams()[self.current_team].direction();
+        let max_jump = self.ops.max_jump();
+        let policy = self.ops.non_finite_policy();
+        let skip_gaps = policy == NonFinitePolicy::SkipGap;
+        let (arena, team) = (&self.arena, self.current_team);
+
+        let result = match mode {
+            FormulaMode::Explicit => {
+                let path = path::explicit(origin, direction, |x| eval(f, &[x]));
+                shot::trace(arena, origin, team, max_jump, skip_gaps, path)
+            }
+            FormulaMode::FirstOrderOde => {
+                let path = path::first_order_ode(origin, direction, |x, y| eval(f, &[x, y]));
+                shot::trace(arena, origin, team, max_jump, skip_gaps, path)
+            }
+            FormulaMode::SecondOrderOde => {
+                let path =
+                    path::second_order_ode(origin, direction, player.slope(), |x, y, dy| {
+                        eval(f, &[x, y, dy])
+                    });
+                shot::trace(arena, origin, team, max_jump, skip_gaps, path)
+            }
+            FormulaMode::Parametric => {
+                let path = path::parametric(
+                    origin,
+                    self.ops.t_max(),
+                    |t| eval(&fs[0], &[t]),
+                    |t| eval(&fs[1], &[t]),
+                );
+                shot::trace(arena, origin, team, max_jump, skip_gaps, path)
+            }
+            FormulaMode::Polar => {
+                // The path sweeps the angle in radians and the formula takes it in the game unit
+                let unit = self.ops.angle_unit().radians();
+                let path = path::polar(
+                    origin,
+                    player.shape().radius(),
+                    self.ops.theta_max_radians(),
+                    self.ops.polar_rotation(),
+                    |theta| eval(f, &[theta / unit]),
+                );
+                shot::trace(arena, origin, team, max_jump, skip_gaps, path)
+            }
+        };
 
-        let player = self.get_current_player_mut();
-        player.set_formula(formula.into());
+        if steps.get() > limits.max_steps() {
+            return Err(ShotError::TooManySteps {
+                max: limits.max_steps(),
+            });
+        }
 
-        Ok(())
+        if policy == NonFinitePolicy::Reject && result.cause() == Impact::NonFinite {
+            let at = result.impact();
+            return Err(ShotError::NonFinite { x: at.x, y: at.y });
+        }
+
+        Ok(result)
     }
 
     pub fn next_team(&mut self) {
         let teams = self.arena.get_teams();
-        assert!(teams.len() > 0);
+        assert!(!teams.is_empty());
 
         for i in 1..teams.len() {
             let idx = (self.current_team + i) % teams.len();
@@ -173,6 +393,23 @@ impl Game {
             .unwrap()
     }
 
+    /// Limits of the formulas of the current team
+    fn current_limits(&self) -> formula::Limits {
+        self.ops.limits_for(self.current_team)
+    }
+
+    /// How the formulas of the current player are read, within `limits`
+    fn formula_context<'a>(&'a self, limits: &'a formula::Limits) -> formula::Context<'a> {
+        let mode = self.ops.formula_mode();
+        formula::Context {
+            vars: mode.variables(),
+            components: mode.components(),
+            helpers: self.get_current_player().helpers(),
+            limits,
+            unit: self.ops.angle_unit(),
+        }
+    }
+
     fn get_current_player(&self) -> &Player {
         self.arena.get_teams()[self.current_team]
             .get_current_player()
@@ -182,11 +419,434 @@ impl Game {
 
 #[cfg(test)]
 mod tests {
+    use float_cmp::approx_eq;
+
     use super::*;
+    use crate::formula::{ErrorKind, Limits, Span};
 
     #[test]
     fn test_build() {
         let game = Game::new(20.0, 10.0, 5, 0.2, 2.0, &[4, 4], 1.0, 0);
         assert!(game.is_ok());
     }
+
+    #[test]
+    fn test_shoot_invalid() {
+        let mut game = Game::new(20.0, 10.0, 5, 0.2, 2.0, &[4, 4], 1.0, 0).unwrap();
+
+        assert!(game.shoot("x +").is_err());
+        assert!(game.shoot("y").is_err());
+        assert_eq!(game.get_current_team_idx(), 0);
+    }
+
+    /// Game with one player per team and no obstacles, with the options changed by `setup`
+    fn game_with(setup: impl FnOnce(&mut Options)) -> Game {
+        let mut ops = Options::new(0, 0.2, 2.0, &[1, 1], 1.0, 0);
+        setup(&mut ops);
+        Game::with_options(20.0, 10.0, ops).unwrap()
+    }
+
+    /// Formula of the straight line from the current player to player `player` of team `team`
+    fn aim_line(game: &Game, team: usize, player: usize) -> String {
+        let shooter = game.get_current_player().shape().pos();
+        let target = game.arena.get_teams()[team].get_players()[player].shape().pos();
+
+        format!("{} * x", (target.y - shooter.y) / (target.x - shooter.x))
+    }
+
+    #[test]
+    fn test_shoot_kills_enemy() {
+        let mut game = Game::new(20.0, 10.0, 0, 0.2, 2.0, &[1, 2], 1.0, 0).unwrap();
+        let formula = aim_line(&game, 1, 0);
+
+        let result = game.shoot(&formula).unwrap();
+
+        assert_eq!(result.killed(), &vec![Kill { team: 1, player: 0 }]);
+        assert!(!game.arena.get_teams()[1].get_players()[0].alive());
+        assert_eq!(game.arena.get_teams()[0].get_players()[0].formula(), formula);
+    }
+
+    #[test]
+    fn test_shoot_starts_at_shooter() {
+        let mut game = Game::new(20.0, 10.0, 0, 0.2, 2.0, &[1, 1], 1.0, 0).unwrap();
+        let shooter = game.get_current_player().shape().pos().clone();
+
+        let result = game.shoot("x^2 + 10").unwrap();
+        let start = &result.trajectory()[0];
+
+        assert!(approx_eq!(f64, start.x, shooter.x, epsilon = 1e-9));
+        assert!(approx_eq!(f64, start.y, shooter.y, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_shoot_right_team_goes_left() {
+        let mut game = Game::new(20.0, 10.0, 0, 0.2, 2.0, &[1, 1], 1.0, 0).unwrap();
+        game.shoot("100 * x").unwrap();
+
+        let formula = aim_line(&game, 0, 0);
+        let result = game.shoot(&formula).unwrap();
+
+        assert!(result.trajectory()[1].x < result.trajectory()[0].x);
+        assert_eq!(result.killed(), &vec![Kill { team: 0, player: 0 }]);
+    }
+
+    #[test]
+    fn test_shoot_next_team() {
+        let mut game = Game::new(20.0, 10.0, 5, 0.2, 2.0, &[4, 4], 1.0, 0).unwrap();
+
+        game.shoot("100 * x").unwrap();
+        assert_eq!(game.get_current_team_idx(), 1);
+        game.shoot("100 * x").unwrap();
+        assert_eq!(game.get_current_team_idx(), 0);
+    }
+
+    #[test]
+    fn test_shoot_stores_canonical_formula() {
+        let mut game = Game::new(20.0, 10.0, 5, 0.2, 2.0, &[4, 4], 1.0, 0).unwrap();
+
+        assert_eq!(game.normalize_formula("100x²").unwrap(), "100 * x^2");
+        game.shoot("100x²").unwrap();
+        assert_eq!(game.arena.get_teams()[0].get_players()[0].formula(), "100 * x^2");
+
+        game.shoot(r"\frac{100}{2}\left|x\right|").unwrap();
+        assert_eq!(game.arena.get_teams()[1].get_players()[0].formula(), "100 / 2 * abs(x)");
+
+        let formula = game.arena.get_teams()[1].get_players()[0].formula().to_string();
+        assert_eq!(
+            game.render_formula(&formula, Notation::Latex).unwrap(),
+            r"\frac{100}{2} \cdot \left|x\right|"
+        );
+    }
+
+    /// Shoots a formula that is not defined between 1 and 2 units in front of the shooter
+    fn shoot_with_gap(policy: NonFinitePolicy) -> (Game, Result<ShotResult, ShotError>) {
+        let mut game = game_with(|ops| ops.set_non_finite_policy(policy));
+
+        let x = game.get_current_player().shape().pos().x;
+        let center = x + 1.5 * game.arena.get_teams()[0].direction();
+        let result = game.shoot(&format!("sqrt(abs(x - ({})) - 0.5) / 10", center));
+
+        (game, result)
+    }
+
+    #[test]
+    fn test_non_finite_policy() {
+        let (_, result) = shoot_with_gap(NonFinitePolicy::Stop);
+        let result = result.unwrap();
+        assert_eq!(result.cause(), Impact::NonFinite);
+        assert!(result.gaps().is_empty());
+
+        let (_, result) = shoot_with_gap(NonFinitePolicy::SkipGap);
+        let result = result.unwrap();
+        assert_ne!(result.cause(), Impact::NonFinite);
+        assert_eq!(result.gaps().len(), 1);
+
+        let (game, result) = shoot_with_gap(NonFinitePolicy::Reject);
+        assert!(matches!(result, Err(ShotError::NonFinite { .. })));
+        assert_eq!(game.get_current_team_idx(), 0);
+    }
+
+    #[test]
+    fn test_angle_unit() {
+        let radians = game_with(|ops| ops.set_angle_unit(AngleUnit::Radians));
+        let degrees = game_with(|ops| ops.set_angle_unit(AngleUnit::Degrees));
+
+        // sin(100) is positive in degrees and negative in radians
+        let x = radians.get_current_player().shape().pos().x;
+        let formula = format!("sqrt(sin(x - ({}) + 100))", x);
+        assert!(radians.validate_formula(&formula).has_errors());
+        assert!(!degrees.validate_formula(&formula).has_errors());
+
+        let a = radians.preview("sin(x)").unwrap();
+        let b = degrees.preview("sin(x * 180 / pi)").unwrap();
+        assert!(approx_eq!(f64, a.impact().x, b.impact().x, epsilon = 1e-6));
+        assert!(approx_eq!(f64, a.impact().y, b.impact().y, epsilon = 1e-6));
+
+        assert_eq!(degrees.derivative("sin(x)").unwrap(), "cos(x) * (pi / 180)");
+        assert_eq!(
+            degrees.derivative("2 * asin(x)").unwrap(),
+            "2 * (1 / sqrt(1 - x^2) / (pi / 180))"
+        );
+    }
+
+    #[test]
+    fn test_polar_degrees() {
+        let polar_in = |unit| {
+            game_with(|ops| {
+                ops.set_formula_mode(FormulaMode::Polar);
+                ops.set_angle_unit(unit);
+            })
+        };
+        let radians = polar_in(AngleUnit::Radians);
+        let mut degrees = polar_in(AngleUnit::Degrees);
+        assert!(approx_eq!(f64, degrees.ops.theta_max(), 720.0, epsilon = 1e-9));
+
+        // theta is in degrees like the angles taken by `sin`
+        let same = |a: &ShotResult, b: &ShotResult| {
+            approx_eq!(f64, a.impact().x, b.impact().x, epsilon = 1e-6)
+                && approx_eq!(f64, a.impact().y, b.impact().y, epsilon = 1e-6)
+        };
+        let a = radians.preview("1 + sin(theta)").unwrap();
+        let b = degrees.preview("1 + sin(theta)").unwrap();
+        assert!(same(&a, &b));
+        let a = radians.preview("theta / pi").unwrap();
+        let b = degrees.preview("theta / 180").unwrap();
+        assert!(same(&a, &b));
+
+        // A quarter turn around the shooter, whose radius is 1
+        degrees.ops.set_theta_max(90.0);
+        let result = degrees.shoot("1").unwrap();
+        assert_eq!(result.cause(), Impact::Finished);
+        let (start, end) = (&result.trajectory()[0], result.impact());
+        assert!(approx_eq!(f64, end.x, start.x - 1.0, epsilon = 1e-6));
+        assert!(approx_eq!(f64, end.y, start.y + 1.0, epsilon = 1e-6));
+    }
+
+    #[test]
+    fn test_shoot_limits() {
+        let mut limits = Limits::default();
+        limits.set_max_nodes(3);
+        limits.set_functions(vec!["sin".into()]).unwrap();
+        limits.set_features(Vec::new()).unwrap();
+
+        let mut game = game_with(|ops| ops.set_limits(limits));
+
+        let err = game.shoot("cos(x)").unwrap_err();
+        assert_eq!(
+            err,
+            ShotError::Formula(formula::Error::new(
+                Span::new(0, 6),
+                ErrorKind::ForbiddenFunction("cos".into())
+            ))
+        );
+        assert!(game.validate_formula("x + x^2").has_errors());
+
+        let err = game.shoot("x % 2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The remainder operator `%` is not allowed in this game (at 0..5)"
+        );
+        assert!(game.shoot("x + x^2").is_err());
+
+        game.shoot("sin(x)").unwrap();
+        assert_eq!(game.get_current_team_idx(), 1);
+    }
+
+    #[test]
+    fn test_complexity_handicap() {
+        let mut limits = Limits::default();
+        limits.set_max_complexity(5);
+
+        let mut game = game_with(|ops| {
+            ops.set_limits(limits);
+            ops.set_complexity_handicap(&[0, 4]);
+        });
+
+        assert_eq!(game.formula_complexity("1000 * x + 0.25").unwrap(), 6);
+        assert_eq!(game.get_current_max_complexity(), 5);
+        assert!(game.validate_formula("1000 * x + 0.25").has_errors());
+        let err = game.shoot("1000 * x + 0.25").unwrap_err();
+        assert_eq!(
+            err,
+            ShotError::Formula(formula::Error::new(
+                Span::new(0, 15),
+                ErrorKind::TooComplex {
+                    complexity: 6,
+                    max: 5
+                }
+            ))
+        );
+
+        game.shoot("1000 * x").unwrap();
+        assert_eq!(game.get_current_max_complexity(), 9);
+        assert!(!game.validate_formula("1000 * x + 0.25").has_errors());
+
+        let mut ops = Options::new(0, 0.2, 2.0, &[1, 1], 1.0, 0);
+        ops.set_complexity_handicap(&[0, 4, 2]);
+        assert!(Game::with_options(20.0, 10.0, ops).is_err());
+    }
+
+    #[test]
+    fn test_shoot_too_many_steps() {
+        let mut limits = Limits::default();
+        limits.set_max_steps(100);
+
+        let mut game = game_with(|ops| ops.set_limits(limits));
+
+        assert_eq!(
+            game.shoot("x + 1").unwrap_err(),
+            ShotError::TooManySteps { max: 100 }
+        );
+        assert_eq!(game.get_current_team_idx(), 0);
+    }
+
+    #[test]
+    fn test_derivative() {
+        let game = game_with(|ops| ops.set_formula_mode(FormulaMode::Parametric));
+
+        assert_eq!(game.derivative("t^2, sin(t)").unwrap(), "2 * t, cos(t)");
+        assert!(game.derivative("t^2").is_err());
+    }
+
+    #[test]
+    fn test_helpers() {
+        let mut game = Game::new(20.0, 10.0, 0, 0.2, 2.0, &[1, 1], 1.0, 0).unwrap();
+        game.define_helper("g(x, c) = (x - c) / 100").unwrap();
+
+        let formula = "g(x, 1) * 1000";
+        assert!(!game.validate_formula(formula).has_errors());
+        assert!(game.shoot(formula).is_ok());
+
+        // Helpers belong to the player that defined them
+        assert!(game.validate_formula(formula).has_errors());
+        assert!(game.shoot(formula).is_err());
+        game.next_team();
+
+        assert_eq!(
+            game.define_helper("f(x) = f(x)").unwrap_err().kind,
+            ErrorKind::CyclicDefinition("f".into())
+        );
+        assert!(game.remove_helper("g"));
+        assert!(game.shoot(formula).is_err());
+    }
+
+    #[test]
+    fn test_shoot_discontinuity() {
+        let mut game = Game::new(20.0, 10.0, 0, 0.2, 2.0, &[1, 1], 1.0, 0).unwrap();
+        let shooter = game.get_current_player().shape().pos().clone();
+
+        // Jumps 5 units up half a unit in front of the shooter
+        let formula = format!("5 * signum(x - {})", shooter.x + 0.505);
+        let result = game.shoot(&formula).unwrap();
+
+        assert_eq!(result.cause(), Impact::Discontinuity);
+        assert!(approx_eq!(f64, result.impact().x, shooter.x + 0.5, epsilon = 0.02));
+        assert!(approx_eq!(f64, result.impact().y, shooter.y, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_shoot_piecewise() {
+        let mut game = Game::new(20.0, 10.0, 0, 0.2, 2.0, &[1, 1], 1.0, 0).unwrap();
+        let shooter = game.get_current_player().shape().pos().clone();
+        let a = shooter.x + 0.505;
+
+        // A small step is followed but a large one breaks the shot like any other jump
+        let result = game.clone().shoot(&format!("{{x < {}: 0; 1}}", a)).unwrap();
+        assert!(result.trajectory().iter().any(|p| p.x > a + 1.0));
+
+        let result = game.shoot(&format!("{{x < {}: 0; 5}}", a)).unwrap();
+        assert_eq!(result.cause(), Impact::Discontinuity);
+        assert!(approx_eq!(f64, result.impact().x, shooter.x + 0.5, epsilon = 0.02));
+    }
+
+    #[test]
+    fn test_shoot_step_spares_enemy() {
+        let mut game = Game::new(20.0, 10.0, 0, 0.2, 2.0, &[1, 1], 0.2, 0).unwrap();
+        let shooter = game.get_current_player().shape().pos().clone();
+        let target = game.arena.get_teams()[1].get_players()[0].shape().pos().clone();
+
+        // The step goes straight through the enemy, which a real jump does not hit
+        let height = 2.0 * (target.y - shooter.y) + 3.0 * (target.y - shooter.y).signum();
+        let result = game.shoot(&format!("{{x < {}: 0; {}}}", target.x, height)).unwrap();
+
+        assert_eq!(result.cause(), Impact::Discontinuity);
+        assert!(result.killed().is_empty());
+        assert!(game.arena.get_teams()[1].get_players()[0].alive());
+    }
+
+    #[test]
+    fn test_preview() {
+        let mut game = Game::new(20.0, 10.0, 5, 0.2, 2.0, &[4, 4], 1.0, 0).unwrap();
+
+        let preview = game.preview("sin(x)").unwrap();
+        let result = game.clone().shoot("sin(x)").unwrap();
+
+        assert_eq!(preview.cause(), Impact::Truncated);
+        assert!(approx_eq!(f64, preview.length(), 5.0, epsilon = 1e-9));
+        assert!(preview.killed().is_empty());
+        for (p, q) in preview.trajectory().iter().zip(result.trajectory()).take(10) {
+            assert!(approx_eq!(f64, p.x, q.x) && approx_eq!(f64, p.y, q.y));
+        }
+        assert_eq!(game.get_current_team_idx(), 0);
+        assert!(game.get_current_formula().is_empty());
+
+        game.ops.set_preview_fraction(0.5);
+        let half = game.preview("sin(x)").unwrap();
+        assert!(half.length() <= result.length() / 2.0 + 1e-9);
+
+        game.ops.set_allow_preview(false);
+        assert_eq!(game.preview("sin(x)").unwrap_err(), ShotError::PreviewDisabled);
+    }
+
+    #[test]
+    fn test_shoot_first_order_ode() {
+        let mut game = game_with(|ops| ops.set_formula_mode(FormulaMode::FirstOrderOde));
+
+        let shooter = game.get_current_player().shape().pos().clone();
+        let target = game.arena.get_teams()[1].get_players()[0].shape().pos().clone();
+        let slope = (target.y - shooter.y) / (target.x - shooter.x);
+
+        assert!(game.validate_formula("y").0.is_empty());
+        assert!(game.shoot("z").is_err());
+
+        let result = game.shoot(&slope.to_string()).unwrap();
+        assert_eq!(result.killed(), &vec![Kill { team: 1, player: 0 }]);
+    }
+
+    #[test]
+    fn test_shoot_second_order_ode() {
+        let mut game = game_with(|ops| ops.set_formula_mode(FormulaMode::SecondOrderOde));
+
+        let shooter = game.get_current_player().shape().pos().clone();
+        let target = game.arena.get_teams()[1].get_players()[0].shape().pos().clone();
+        let slope = (target.y - shooter.y) / (target.x - shooter.x);
+
+        assert!(game.validate_formula("-0.1 * dy").0.is_empty());
+        assert!(game.set_current_slope(f64::NAN).is_err());
+
+        game.set_current_slope(slope).unwrap();
+        let result = game.shoot("0").unwrap();
+        assert_eq!(result.killed(), &vec![Kill { team: 1, player: 0 }]);
+    }
+
+    #[test]
+    fn test_shoot_parametric() {
+        let mut game = game_with(|ops| {
+            ops.set_formula_mode(FormulaMode::Parametric);
+            ops.set_t_max(1.0);
+        });
+
+        let shooter = game.get_current_player().shape().pos().clone();
+        let target = game.arena.get_teams()[1].get_players()[0].shape().pos().clone();
+        let formula = format!("{} * t, {} * t", target.x - shooter.x, target.y - shooter.y);
+
+        assert!(game.shoot("t").is_err());
+
+        let result = game.shoot(&formula).unwrap();
+        assert_eq!(result.cause(), Impact::Finished);
+        assert_eq!(result.killed(), &vec![Kill { team: 1, player: 0 }]);
+    }
+
+    #[test]
+    fn test_shoot_polar() {
+        let mut game = game_with(|ops| ops.set_formula_mode(FormulaMode::Polar));
+
+        let shooter = game.get_current_player().shape().pos().clone();
+        let target = game.arena.get_teams()[1].get_players()[0].shape().pos().clone();
+        let angle = (target.y - shooter.y)
+            .atan2(target.x - shooter.x)
+            .rem_euclid(2.0 * std::f64::consts::PI);
+        let dist = shooter.distance_to(&target) - 1.0;
+
+        // Circle once around the shooter, then spiral out to reach the target
+        let formula = format!(
+            "{} * min(1, max(0, (theta - {}) / 0.5))",
+            dist,
+            angle + 2.0 * std::f64::consts::PI - 0.5
+        );
+        assert!(!game.validate_formula(&formula).has_errors());
+
+        let result = game.shoot(&formula).unwrap();
+        assert_eq!(result.killed(), &vec![Kill { team: 1, player: 0 }]);
+    }
 }
@@ -6,7 +6,7 @@ use wasm_bindgen::prelude::wasm_bindgen;
 
 use crate::geometry::{
     math::Point,
-    Circle, Rectangle,
+    Circle, Rectangle, Segment,
 };
 
 use super::Team;
@@ -60,6 +60,10 @@ impl Arena {
         }
     }
 
+    pub fn add_obstacle(&mut self, shape: Circle) {
+        self.obstacles.push(shape);
+    }
+
     pub fn add_obstacles<R: Rng + ?Sized>(
         &mut self,
         num_obstacles: usize,
@@ -76,12 +80,13 @@ impl Arena {
                 .clamp(min_obstacle_size, max_obstacle_size);
 
             let shape = self.find_random_pos(obstacle_size, rng)?;
-            self.obstacles.push(shape);
+            self.add_obstacle(shape);
         }
 
         Ok(())
     }
 
+    #[allow(clippy::ptr_arg, clippy::needless_borrow)]
     pub fn add_teams<R: Rng + ?Sized>(
         &mut self,
         players_per_team: &Vec<usize>,
@@ -100,17 +105,47 @@ impl Arena {
     }
 
     /// True if there is a collision with a player
+    #[allow(clippy::needless_borrow)]
     pub fn collision_with_player(&self, shape: &Circle) -> bool {
         self.teams.iter().any(|t| t.collision_with_player(&shape))
     }
 
     /// True if there is a collision with an obstacle
+    #[allow(clippy::needless_borrow)]
     pub fn collision_with_obstacle(&self, shape: &Circle) -> bool {
         self.obstacles
             .iter()
             .any(|o| o.collision_circle(&shape))
     }
 
+    /// Fraction of `seg`, between 0 and 1, at which it first touches an obstacle, if it does
+    pub fn obstacle_hit(&self, seg: &Segment) -> Option<f64> {
+        self.obstacles
+            .iter()
+            .filter_map(|o| o.intersect_segment(seg))
+            .reduce(f64::min)
+    }
+
+    /// Indices `(team, player)` of the alive players touched by `seg`, ignoring the players of
+    /// team `skip_team`
+    pub fn players_hit(&self, seg: &Segment, skip_team: usize) -> Vec<(usize, usize)> {
+        let mut hits = Vec::new();
+
+        for (t_idx, team) in self.teams.iter().enumerate() {
+            if t_idx == skip_team {
+                continue;
+            }
+
+            for (p_idx, player) in team.get_players().iter().enumerate() {
+                if player.alive() && player.shape().intersect_segment(seg).is_some() {
+                    hits.push((t_idx, p_idx));
+                }
+            }
+        }
+
+        hits
+    }
+
     pub fn get_area(&self) -> &Rectangle {
         &self.area
     }
@@ -0,0 +1,359 @@
+use wasm_bindgen::prelude::*;
+
+use crate::formula::{AngleUnit, Limits};
+
+/// How the formula of a shot describes the trajectory of the projectile
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormulaMode {
+    /// The formula is the curve `y = f(x)`
+    Explicit,
+    /// The formula is the slope of the curve, `y' = f(x, y)`
+    FirstOrderOde,
+    /// The formula is the second derivative of the curve, `y'' = f(x, y, dy)` where `dy` is `y'`.
+    /// The shooter chooses the initial slope.
+    SecondOrderOde,
+    /// The formula is a pair `x(t), y(t)` followed from `t = 0`, moved so that it starts at the
+    /// shooter
+    Parametric,
+    /// The formula is the distance `r(theta)` to the shooter, counted from the border of its
+    /// circle, while the angle `theta` sweeps around it
+    Polar,
+}
+
+/// What a shot does where its formula is not a finite number, like `sqrt(x)` for negative `x`,
+/// `ln(x)` for `x <= 0` or values that overflow to infinity
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    /// The projectile stops at the last point where the formula is finite
+    Stop,
+    /// The projectile disappears and comes back where the formula is finite again, leaving a gap
+    /// in its trajectory
+    SkipGap,
+    /// The shot is rejected without changing the game
+    Reject,
+}
+
+/// Direction in which angles grow
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    CounterClockwise,
+    Clockwise,
+}
+
+impl Rotation {
+    /// Sign of the angles swept in this direction
+    pub fn sign(&self) -> f64 {
+        match self {
+            Rotation::CounterClockwise => 1.0,
+            Rotation::Clockwise => -1.0,
+        }
+    }
+}
+
+impl FormulaMode {
+    /// Variables a formula can use in this mode, in the order they are given to the evaluator
+    pub fn variables(&self) -> &'static [&'static str] {
+        match self {
+            FormulaMode::Explicit => &["x"],
+            FormulaMode::FirstOrderOde => &["x", "y"],
+            FormulaMode::SecondOrderOde => &["x", "y", "dy"],
+            FormulaMode::Parametric => &["t"],
+            FormulaMode::Polar => &["theta"],
+        }
+    }
+
+    /// Number of comma separated formulas expected in this mode
+    pub fn components(&self) -> usize {
+        match self {
+            FormulaMode::Parametric => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Settings of a match, fixed when the game is created
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Options {
+    num_obstacles: usize,
+    min_obstacle_size: f64,
+    max_obstacle_size: f64,
+    players_per_team: Vec<usize>,
+    player_radius: f64,
+    seed: u64,
+    formula_mode: FormulaMode,
+    t_max: f64,
+    theta_max: f64,
+    polar_rotation: Rotation,
+    angle_unit: AngleUnit,
+    max_jump: f64,
+    non_finite_policy: NonFinitePolicy,
+    limits: Limits,
+    complexity_handicap: Vec<usize>,
+    allow_preview: bool,
+    preview_length: f64,
+    preview_fraction: f64,
+}
+
+#[wasm_bindgen]
+impl Options {
+    /// Options for an arena with `num_obstacles` obstacles with a size between
+    /// `min_obstacle_size` and `max_obstacle_size`, and as many teams as `players_per_team` has
+    /// entries, with players of radius `player_radius`. The remaining settings take their default
+    /// value and can be changed through their setters.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        num_obstacles: usize,
+        min_obstacle_size: f64,
+        max_obstacle_size: f64,
+        players_per_team: &[usize],
+        player_radius: f64,
+        seed: u64,
+    ) -> Options {
+        Options {
+            num_obstacles,
+            min_obstacle_size,
+            max_obstacle_size,
+            players_per_team: players_per_team.to_vec(),
+            player_radius,
+            seed,
+            formula_mode: FormulaMode::Explicit,
+            t_max: 10.0,
+            theta_max: 4.0 * std::f64::consts::PI,
+            polar_rotation: Rotation::CounterClockwise,
+            angle_unit: AngleUnit::Radians,
+            max_jump: 2.0,
+            non_finite_policy: NonFinitePolicy::Stop,
+            limits: Limits::default(),
+            complexity_handicap: Vec::new(),
+            allow_preview: true,
+            preview_length: 5.0,
+            preview_fraction: 1.0,
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn formula_mode(&self) -> FormulaMode {
+        self.formula_mode
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_formula_mode(&mut self, mode: FormulaMode) {
+        self.formula_mode = mode;
+    }
+
+    /// Largest value of `t` reached by a [`FormulaMode::Parametric`] shot
+    #[wasm_bindgen(getter)]
+    pub fn t_max(&self) -> f64 {
+        self.t_max
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_t_max(&mut self, t_max: f64) {
+        self.t_max = t_max;
+    }
+
+    /// Largest angle swept by a [`FormulaMode::Polar`] shot, in the angle unit of the game.
+    /// Changing the unit keeps the same sweep.
+    #[wasm_bindgen(getter)]
+    pub fn theta_max(&self) -> f64 {
+        self.theta_max / self.angle_unit.radians()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_theta_max(&mut self, theta_max: f64) {
+        self.theta_max = theta_max * self.angle_unit.radians();
+    }
+
+    /// Direction in which a [`FormulaMode::Polar`] shot sweeps around the shooter
+    #[wasm_bindgen(getter)]
+    pub fn polar_rotation(&self) -> Rotation {
+        self.polar_rotation
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_polar_rotation(&mut self, rotation: Rotation) {
+        self.polar_rotation = rotation;
+    }
+
+    /// Unit of the angles in formulas, taken by `sin`, `cos` and `tan` and given by their
+    /// inverses, and of the angle `theta` of [`FormulaMode::Polar`] shots
+    #[wasm_bindgen(getter)]
+    pub fn angle_unit(&self) -> AngleUnit {
+        self.angle_unit
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_angle_unit(&mut self, unit: AngleUnit) {
+        self.angle_unit = unit;
+    }
+
+    /// Largest jump the projectile can follow where its curve is not continuous. A longer jump is
+    /// taken as a discontinuity of the formula and stops the shot before it.
+    #[wasm_bindgen(getter)]
+    pub fn max_jump(&self) -> f64 {
+        self.max_jump
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_jump(&mut self, max_jump: f64) {
+        self.max_jump = max_jump;
+    }
+
+    /// What shots do where their formula is not a finite number
+    #[wasm_bindgen(getter)]
+    pub fn non_finite_policy(&self) -> NonFinitePolicy {
+        self.non_finite_policy
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_non_finite_policy(&mut self, policy: NonFinitePolicy) {
+        self.non_finite_policy = policy;
+    }
+
+    /// Bounds on the formulas players can shoot
+    #[wasm_bindgen(getter)]
+    pub fn limits(&self) -> Limits {
+        self.limits.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Complexity allowed to each team on top of the maximum complexity of the limits, by team
+    /// index, to give weaker teams a higher budget. Teams without an entry get no extra
+    /// complexity.
+    #[wasm_bindgen(getter)]
+    pub fn complexity_handicap(&self) -> Vec<usize> {
+        self.complexity_handicap.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_complexity_handicap(&mut self, handicap: &[usize]) {
+        self.complexity_handicap = handicap.to_vec();
+    }
+
+    /// Whether players can preview the beginning of their shots before shooting
+    #[wasm_bindgen(getter)]
+    pub fn allow_preview(&self) -> bool {
+        self.allow_preview
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_allow_preview(&mut self, allow_preview: bool) {
+        self.allow_preview = allow_preview;
+    }
+
+    /// Longest arc length shown by a preview
+    #[wasm_bindgen(getter)]
+    pub fn preview_length(&self) -> f64 {
+        self.preview_length
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_preview_length(&mut self, preview_length: f64) {
+        self.preview_length = preview_length;
+    }
+
+    /// Largest fraction of the arc length of the shot shown by a preview, between 0 and 1
+    #[wasm_bindgen(getter)]
+    pub fn preview_fraction(&self) -> f64 {
+        self.preview_fraction
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_preview_fraction(&mut self, preview_fraction: f64) {
+        self.preview_fraction = preview_fraction;
+    }
+}
+
+impl Options {
+    pub fn num_obstacles(&self) -> usize {
+        self.num_obstacles
+    }
+
+    pub fn min_obstacle_size(&self) -> f64 {
+        self.min_obstacle_size
+    }
+
+    pub fn max_obstacle_size(&self) -> f64 {
+        self.max_obstacle_size
+    }
+
+    pub fn players_per_team(&self) -> &Vec<usize> {
+        &self.players_per_team
+    }
+
+    pub fn player_radius(&self) -> f64 {
+        self.player_radius
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Largest angle swept by a [`FormulaMode::Polar`] shot, in radians
+    pub fn theta_max_radians(&self) -> f64 {
+        self.theta_max
+    }
+
+    pub fn limits_ref(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Limits that apply to the formulas of the team `team`, including its handicap
+    pub fn limits_for(&self, team: usize) -> Limits {
+        let mut limits = self.limits.clone();
+        let handicap = self.complexity_handicap.get(team).copied().unwrap_or(0);
+        limits.set_max_complexity(limits.max_complexity().saturating_add(handicap));
+        limits
+    }
+
+    /// Checks that the options describe a game that can be played
+    pub fn validate(&self) -> Result<(), String> {
+        if self.players_per_team.len() < 2 {
+            return Err("There must be at least two teams".into());
+        }
+
+        if self.min_obstacle_size <= 0. || self.player_radius <= 0. {
+            return Err("Obstacle size and player radius must be a positive value".into());
+        }
+
+        if !(self.t_max > 0. && self.t_max.is_finite()) {
+            return Err("t_max must be a finite positive value".into());
+        }
+
+        if !(self.theta_max > 0. && self.theta_max.is_finite()) {
+            return Err("theta_max must be a finite positive value".into());
+        }
+
+        if !(self.max_jump > 0. && self.max_jump.is_finite()) {
+            return Err("max_jump must be a finite positive value".into());
+        }
+
+        if self.complexity_handicap.len() > self.players_per_team.len() {
+            return Err("There are more complexity handicaps than teams".into());
+        }
+
+        if self.preview_length.is_nan() || self.preview_length < 0. {
+            return Err("preview_length must not be negative".into());
+        }
+
+        if !(0. ..=1.).contains(&self.preview_fraction) {
+            return Err("preview_fraction must be between 0 and 1".into());
+        }
+
+        if self.min_obstacle_size > self.max_obstacle_size {
+            return Err(
+                "The maximum obstacle size must be at least the minimum obstacle size".into(),
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+//! Generators of the points followed by a projectile. Paths advance `STEP` at a time along their
+//! parameter, which is x for the curves of the form `y(x)`, and every step is split again where
+//! the curve is steep or bends, so that the segments between consecutive points follow the curve
+//! closely. Where a step cannot be split finely enough because the curve jumps, like `floor(x)`
+//! does at whole numbers, the point after the jump is marked as such. Paths are endless unless
+//! their parameter has an upper limit, it is up to the tracer to decide when to stop. Curves of
+//! the form `y(x)` take a `direction`, 1 or -1, telling whether x grows or decreases along the
+//! path.
+
+use super::Rotation;
+use crate::geometry::{Point, Segment};
+
+/// Distance between two consecutive values of the parameter of a path, before refining it
+pub const STEP: f64 = 0.01;
+
+/// Longest segment between two consecutive points of a path
+pub const MAX_SEGMENT: f64 = 0.05;
+
+/// Largest distance allowed between a curve and the segment standing for it
+pub const TOLERANCE: f64 = 1e-3;
+
+/// Number of times a step can be split in halves, so that steps are never shorter than
+/// `STEP / 2^MAX_SPLITS`
+const MAX_SPLITS: u32 = 10;
+
+/// A point of a path
+#[derive(Clone, Debug)]
+pub struct Waypoint {
+    pub pos: Point,
+    /// True if the curve jumps to this point from the previous one instead of going through the
+    /// points between them
+    pub jump: bool,
+}
+
+/// A point of a path along with what is needed to continue it, like the value of the parameter
+#[derive(Clone)]
+struct Sample<T> {
+    pos: Point,
+    state: T,
+}
+
+/// Follows the path that `step` advances by `h` from `start`. Yields, for each step, the samples
+/// reached once the step is split where needed, each telling whether the curve jumps to it.
+fn adaptive<T: Clone, F: Fn(&Sample<T>, f64) -> Sample<T>>(
+    start: Sample<T>,
+    h: f64,
+    step: F,
+) -> impl Iterator<Item = Vec<(Sample<T>, bool)>> {
+    std::iter::successors(Some(vec![(start, false)]), move |samples| {
+        let (last, _) = samples.last()?;
+        let mut out = Vec::new();
+        refine(last, step(last, h), h, 0, &step, &mut out);
+        Some(out)
+    })
+}
+
+/// Pushes the samples that go from `start` to `end`, one step of `h` apart, splitting the step in
+/// halves until the curve between two samples is short and close to a straight line
+fn refine<T: Clone, F: Fn(&Sample<T>, f64) -> Sample<T>>(
+    start: &Sample<T>,
+    end: Sample<T>,
+    h: f64,
+    splits: u32,
+    step: &F,
+    out: &mut Vec<(Sample<T>, bool)>,
+) {
+    let finite = end.pos.x.is_finite() && end.pos.y.is_finite();
+    if !finite {
+        out.push((end, false));
+        return;
+    }
+
+    let mid = step(start, h / 2.0);
+    let chord = Segment::new(start.pos.clone(), end.pos.clone());
+    if chord.length() <= MAX_SEGMENT && chord.distance_to(&mid.pos) <= TOLERANCE {
+        out.push((end, false));
+        return;
+    }
+
+    if splits == MAX_SPLITS {
+        // A steep curve still moves about as much over each half of the step, while one that
+        // jumps makes nearly all of its move over one of them
+        let halves = (
+            start.pos.distance_to(&mid.pos),
+            mid.pos.distance_to(&end.pos),
+        );
+        let jump = halves.0.max(halves.1) > 0.75 * chord.length();
+        out.push((end, jump));
+        return;
+    }
+
+    refine(start, mid, h / 2.0, splits + 1, step, out);
+    let (half, _) = out.last().unwrap().clone();
+    let end = step(&half, h / 2.0);
+    refine(&half, end, h / 2.0, splits + 1, step, out);
+}
+
+/// Waypoints of the samples yielded by [`adaptive`]
+fn points<T, I: Iterator<Item = Vec<(Sample<T>, bool)>>>(
+    samples: I,
+) -> impl Iterator<Item = Waypoint> {
+    samples.flat_map(|s| s.into_iter().map(|(s, jump)| Waypoint { pos: s.pos, jump }))
+}
+
+/// Samples the curve `y = f(x)`, shifted vertically so that it goes through `origin` like in
+/// Graphwar
+pub fn explicit<F: Fn(f64) -> f64>(
+    origin: &Point,
+    direction: f64,
+    f: F,
+) -> impl Iterator<Item = Waypoint> {
+    let offset = origin.y - f(origin.x);
+    let start = Sample {
+        pos: origin.clone(),
+        state: (),
+    };
+
+    points(adaptive(start, direction * STEP, move |s, h| {
+        let x = s.pos.x + h;
+        Sample {
+            pos: Point::new(x, f(x) + offset),
+            state: (),
+        }
+    }))
+}
+
+/// Integrates `y' = f(x, y)` from `origin` with the classic fourth order Runge-Kutta method
+pub fn first_order_ode<F: Fn(f64, f64) -> f64>(
+    origin: &Point,
+    direction: f64,
+    f: F,
+) -> impl Iterator<Item = Waypoint> {
+    let start = Sample {
+        pos: origin.clone(),
+        state: (),
+    };
+
+    points(adaptive(start, direction * STEP, move |s, h| {
+        let (x, y) = (s.pos.x, s.pos.y);
+
+        let k1 = f(x, y);
+        let k2 = f(x + h / 2.0, y + h / 2.0 * k1);
+        let k3 = f(x + h / 2.0, y + h / 2.0 * k2);
+        let k4 = f(x + h, y + h * k3);
+
+        Sample {
+            pos: Point::new(x + h, y + h / 6.0 * (k1 + 2.0 * k2 + 2.0 * k3 + k4)),
+            state: (),
+        }
+    }))
+}
+
+/// Integrates `y'' = f(x, y, y')` from `origin` with an initial slope of `slope`, using the
+/// classic fourth order Runge-Kutta method on the system `y' = v`, `v' = f(x, y, v)`
+pub fn second_order_ode<F: Fn(f64, f64, f64) -> f64>(
+    origin: &Point,
+    direction: f64,
+    slope: f64,
+    f: F,
+) -> impl Iterator<Item = Waypoint> {
+    let start = Sample {
+        pos: origin.clone(),
+        state: slope,
+    };
+
+    points(adaptive(start, direction * STEP, move |s, h| {
+        let (x, y, v) = (s.pos.x, s.pos.y, s.state);
+
+        let (ky1, kv1) = (v, f(x, y, v));
+        let (ky2, kv2) = (
+            v + h / 2.0 * kv1,
+            f(x + h / 2.0, y + h / 2.0 * ky1, v + h / 2.0 * kv1),
+        );
+        let (ky3, kv3) = (
+            v + h / 2.0 * kv2,
+            f(x + h / 2.0, y + h / 2.0 * ky2, v + h / 2.0 * kv2),
+        );
+        let (ky4, kv4) = (v + h * kv3, f(x + h, y + h * ky3, v + h * kv3));
+
+        Sample {
+            pos: Point::new(x + h, y + h / 6.0 * (ky1 + 2.0 * ky2 + 2.0 * ky3 + ky4)),
+            state: v + h / 6.0 * (kv1 + 2.0 * kv2 + 2.0 * kv3 + kv4),
+        }
+    }))
+}
+
+/// Samples the curve `(fx(t), fy(t))` for `t` between 0 and `t_max`, moved so that it starts at
+/// `origin`
+pub fn parametric<F: Fn(f64) -> f64, G: Fn(f64) -> f64>(
+    origin: &Point,
+    t_max: f64,
+    fx: F,
+    fy: G,
+) -> impl Iterator<Item = Waypoint> {
+    let shift = Point::new(origin.x - fx(0.0), origin.y - fy(0.0));
+    let steps = (t_max / STEP).ceil() as usize;
+    let start = Sample {
+        pos: origin.clone(),
+        state: 0.0,
+    };
+
+    let samples = adaptive(start, STEP, move |s, h| {
+        let t = (s.state + h).min(t_max);
+        Sample {
+            pos: Point::new(shift.x + fx(t), shift.y + fy(t)),
+            state: t,
+        }
+    });
+
+    points(samples.take(steps + 1))
+}
+
+/// Samples the polar curve `r(theta)` around `center` for `theta` between 0 and `theta_max`. The
+/// distance to the centre is `radius + r(theta) - r(0)`, so the curve starts at the border of
+/// the circle of radius `radius`, and the angle grows in the direction of `rotation`.
+pub fn polar<F: Fn(f64) -> f64>(
+    center: &Point,
+    radius: f64,
+    theta_max: f64,
+    rotation: Rotation,
+    r: F,
+) -> impl Iterator<Item = Waypoint> {
+    let (center, offset) = (center.clone(), radius - r(0.0));
+    let sign = rotation.sign();
+    let steps = (theta_max / STEP).ceil() as usize;
+
+    let sample = move |theta: f64| {
+        let dist = offset + r(theta);
+        let angle = sign * theta;
+        Sample {
+            pos: Point::new(center.x + dist * angle.cos(), center.y + dist * angle.sin()),
+            state: theta,
+        }
+    };
+
+    let samples = adaptive(sample(0.0), STEP, move |s, h| {
+        sample((s.state + h).min(theta_max))
+    });
+
+    points(samples.take(steps + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use float_cmp::approx_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_explicit() {
+        let p = explicit(&Point::new(1.0, 2.0), 1.0, |x| 2.0 * x)
+            .nth(100)
+            .unwrap()
+            .pos;
+
+        assert!(approx_eq!(f64, p.x, 2.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, p.y, 4.0, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_explicit_anchored() {
+        let path: Vec<Point> = explicit(&Point::new(2.0, -1.0), -1.0, |x| x * x)
+            .map(|w| w.pos)
+            .take(101)
+            .collect();
+
+        assert!(approx_eq!(f64, path[0].x, 2.0) && approx_eq!(f64, path[0].y, -1.0));
+        assert!(approx_eq!(f64, path[100].x, 1.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, path[100].y, -4.0, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_explicit_refined() {
+        let f = |x: f64| 100.0 * x + (50.0 * x).sin();
+        let path: Vec<Point> = explicit(&Point::new(0.0, 0.0), 1.0, f)
+            .map(|w| w.pos)
+            .take_while(|p| p.x < 1.0)
+            .collect();
+
+        for w in path.windows(2) {
+            let chord = Segment::new(w[0].clone(), w[1].clone());
+            let x = (w[0].x + w[1].x) / 2.0;
+
+            assert!(chord.length() <= MAX_SEGMENT);
+            assert!(chord.distance_to(&Point::new(x, f(x))) <= 2.0 * TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn test_first_order_ode() {
+        // y' = y with y(0) = 1 is the exponential
+        let p = first_order_ode(&Point::new(0.0, 1.0), 1.0, |_, y| y)
+            .nth(100)
+            .unwrap()
+            .pos;
+
+        assert!(approx_eq!(f64, p.x, 1.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, p.y, std::f64::consts::E, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_second_order_ode() {
+        // y'' = -y with y(0) = 0 and y'(0) = 1 is the sine
+        let p = second_order_ode(&Point::new(0.0, 0.0), 1.0, 1.0, |_, y, _| -y)
+            .nth(100)
+            .unwrap()
+            .pos;
+
+        assert!(approx_eq!(f64, p.x, 1.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, p.y, 1.0_f64.sin(), epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_parametric() {
+        let path: Vec<Point> = parametric(&Point::new(1.0, 1.0), 1.0, |t| t.cos(), |t| t.sin())
+            .map(|w| w.pos)
+            .collect();
+        let last = path.last().unwrap();
+
+        assert_eq!(path.len(), 101);
+        assert!(approx_eq!(f64, path[0].x, 1.0) && approx_eq!(f64, path[0].y, 1.0));
+        assert!(approx_eq!(f64, last.x, 1.0_f64.cos(), epsilon = 1e-9));
+        assert!(approx_eq!(f64, last.y, 1.0 + 1.0_f64.sin(), epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_polar() {
+        let center = Point::new(1.0, 1.0);
+        let path: Vec<Point> = polar(&center, 0.5, PI, Rotation::Clockwise, |t| t)
+            .map(|w| w.pos)
+            .collect();
+        let quarter = &path[(PI / 2.0 / STEP) as usize];
+        let last = path.last().unwrap();
+
+        assert!(approx_eq!(f64, path[0].x, 1.5) && approx_eq!(f64, path[0].y, 1.0));
+        assert!(approx_eq!(f64, quarter.x, 1.0, epsilon = 0.02));
+        assert!(approx_eq!(
+            f64,
+            quarter.y,
+            1.0 - 0.5 - PI / 2.0,
+            epsilon = 0.02
+        ));
+        assert!(approx_eq!(f64, last.x, 1.0 - 0.5 - PI, epsilon = 1e-9));
+        assert!(approx_eq!(f64, last.y, 1.0, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_second_order_ode_deterministic() {
+        let f = |x: f64, y: f64, dy: f64| (x * y).sin() - 0.3 * dy;
+        let a: Vec<Point> = second_order_ode(&Point::new(-3.0, 1.0), -1.0, 0.5, f)
+            .map(|w| w.pos)
+            .take(1000)
+            .collect();
+        let b: Vec<Point> = second_order_ode(&Point::new(-3.0, 1.0), -1.0, 0.5, f)
+            .map(|w| w.pos)
+            .take(1000)
+            .collect();
+
+        assert!(a
+            .iter()
+            .zip(&b)
+            .all(|(p, q)| p.x.to_bits() == q.x.to_bits() && p.y.to_bits() == q.y.to_bits()));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::formula::Helpers;
 use crate::geometry::*;
 use serde::Serialize;
 use tsify::Tsify;
@@ -10,6 +11,8 @@ pub struct Player {
     shape: Circle,
     alive: bool,
     formula: String,
+    slope: f64,
+    helpers: Helpers,
 }
 
 impl Player {
@@ -18,6 +21,8 @@ impl Player {
             shape,
             alive: true,
             formula: String::new(),
+            slope: 0.0,
+            helpers: Helpers::new(),
         }
     }
 
@@ -26,6 +31,8 @@ impl Player {
             shape: Circle::new(pos, radius),
             alive: true,
             formula: String::new(),
+            slope: 0.0,
+            helpers: Helpers::new(),
         }
     }
 
@@ -44,6 +51,19 @@ impl Player {
     pub fn set_formula(&mut self, formula: String) {
         self.formula = formula;
     }
+
+    /// Initial slope of the shots of the player in the second order ODE mode
+    pub fn slope(&self) -> f64 {
+        self.slope
+    }
+
+    pub fn set_slope(&mut self, slope: f64) {
+        self.slope = slope;
+    }
+
+    pub fn kill(&mut self) {
+        self.alive = false;
+    }
 }
 
 impl Player {
@@ -54,4 +74,13 @@ impl Player {
     pub fn formula(&self) -> &str {
         &self.formula
     }
+
+    /// Functions defined by the player for their formulas
+    pub fn helpers(&self) -> &Helpers {
+        &self.helpers
+    }
+
+    pub fn helpers_mut(&mut self) -> &mut Helpers {
+        &mut self.helpers
+    }
 }
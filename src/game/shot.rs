@@ -0,0 +1,449 @@
+use std::fmt;
+
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use super::path::Waypoint;
+use super::Arena;
+use crate::formula;
+use crate::geometry::{Point, Segment};
+
+/// Reason why a shot stopped
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub enum Impact {
+    /// The projectile hit an obstacle
+    Obstacle,
+    /// The projectile left the arena
+    Wall,
+    /// The formula evaluated to NaN or infinity, or the curve never came back into the arena
+    /// after a gap where it did
+    NonFinite,
+    /// The curve jumped further than allowed between two consecutive samples, like `tan(x)` does
+    /// at its asymptotes
+    Discontinuity,
+    /// The curve reached the end of its parameter range
+    Finished,
+    /// Only the beginning of the shot was traced, for a preview
+    Truncated,
+}
+
+/// A player killed by a shot
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct Kill {
+    pub team: usize,
+    pub player: usize,
+}
+
+/// Outcome of a shot, with everything needed to draw or replay it
+#[derive(Clone, Debug, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ShotResult {
+    trajectory: Vec<Point>,
+    impact: Point,
+    cause: Impact,
+    killed: Vec<Kill>,
+    length: f64,
+    gaps: Vec<usize>,
+}
+
+impl ShotResult {
+    /// Sampled points of the curve, in the order the projectile travelled them
+    pub fn trajectory(&self) -> &Vec<Point> {
+        &self.trajectory
+    }
+
+    /// Point where the projectile stopped
+    pub fn impact(&self) -> &Point {
+        &self.impact
+    }
+
+    pub fn cause(&self) -> Impact {
+        self.cause
+    }
+
+    pub fn killed(&self) -> &Vec<Kill> {
+        &self.killed
+    }
+
+    /// Arc length travelled by the projectile
+    pub fn length(&self) -> f64 {
+        self.length
+    }
+
+    /// Indices of the points of the trajectory where the projectile reappears after skipping a
+    /// gap where the formula is not finite. The trajectory is not drawn between such a point and
+    /// the previous one.
+    pub fn gaps(&self) -> &Vec<usize> {
+        &self.gaps
+    }
+
+    /// The first `max_length` of the shot, without the players it kills. The shot is left as it
+    /// is if it is not longer than that.
+    pub fn truncate(self, max_length: f64) -> ShotResult {
+        let mut trajectory: Vec<Point> = self.trajectory.iter().take(1).cloned().collect();
+        let mut length = 0.0;
+
+        for (i, w) in self.trajectory.windows(2).enumerate() {
+            if self.gaps.contains(&(i + 1)) {
+                trajectory.push(w[1].clone());
+                continue;
+            }
+
+            let seg = Segment::new(w[0].clone(), w[1].clone());
+            if length + seg.length() > max_length {
+                trajectory.push(seg.point_at((max_length - length) / seg.length()));
+                let gaps = self
+                    .gaps
+                    .into_iter()
+                    .filter(|&g| g < trajectory.len())
+                    .collect();
+
+                return ShotResult {
+                    impact: trajectory.last().unwrap().clone(),
+                    trajectory,
+                    cause: Impact::Truncated,
+                    killed: Vec::new(),
+                    length: max_length,
+                    gaps,
+                };
+            }
+
+            length += seg.length();
+            trajectory.push(w[1].clone());
+        }
+
+        ShotResult {
+            killed: Vec::new(),
+            ..self
+        }
+    }
+}
+
+/// Reason why a formula could not be shot
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+#[serde(tag = "kind", content = "data")]
+pub enum ShotError {
+    /// The formula cannot be read, or breaks the limits of the game
+    Formula(formula::Error),
+    /// Evaluating the formula along the curve takes more steps than allowed
+    TooManySteps { max: usize },
+    /// The game does not allow previewing shots
+    PreviewDisabled,
+    /// The formula stops being a finite number along the curve, past the point `(x, y)`, and the
+    /// game rejects such formulas
+    NonFinite { x: f64, y: f64 },
+}
+
+impl From<formula::Error> for ShotError {
+    fn from(e: formula::Error) -> ShotError {
+        ShotError::Formula(e)
+    }
+}
+
+impl fmt::Display for ShotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShotError::Formula(e) => write!(f, "{}", e),
+            ShotError::TooManySteps { max } => write!(
+                f,
+                "The formula needs more than {} evaluation steps to be shot",
+                max
+            ),
+            ShotError::PreviewDisabled => write!(f, "Previews are disabled in this game"),
+            ShotError::NonFinite { x, y } => write!(
+                f,
+                "The formula is not a finite number past ({}, {}), which is not allowed in this \
+                 game",
+                x, y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShotError {}
+
+impl From<ShotError> for JsValue {
+    fn from(e: ShotError) -> JsValue {
+        serde_wasm_bindgen::to_value(&e).unwrap_or_else(|_| JsValue::from_str(&e.to_string()))
+    }
+}
+
+/// Follows `path` until it ends, leaves the arena, hits an obstacle, reaches a non-finite value or
+/// jumps further than `max_jump`. The path is taken as straight between its samples, even steep
+/// ones, and the projectile stops exactly where it meets a wall or an obstacle. Every enemy of
+/// `team` crossed on the way is reported as killed. `origin` is the position of the shooter.
+///
+/// With `skip_gaps`, non-finite values do not stop the projectile, which disappears and comes
+/// back where the path is finite again, as long as that is inside the arena. Nothing is hit
+/// while in a gap.
+pub fn trace<I: Iterator<Item = Waypoint>>(
+    arena: &Arena,
+    origin: &Point,
+    team: usize,
+    max_jump: f64,
+    skip_gaps: bool,
+    mut path: I,
+) -> ShotResult {
+    let area = arena.get_area();
+    let mut trajectory: Vec<Point> = Vec::new();
+    let mut killed: Vec<Kill> = Vec::new();
+    let mut gaps: Vec<usize> = Vec::new();
+    let mut in_gap = false;
+
+    let cause = path
+        .find_map(|Waypoint { pos, jump }| {
+            if !(pos.x.is_finite() && pos.y.is_finite()) {
+                // The coordinate that is still finite tells when the gap leaves the arena
+                let outside = (pos.x.is_finite() && !(area.left()..=area.right()).contains(&pos.x))
+                    || (pos.y.is_finite() && !(area.bottom()..=area.top()).contains(&pos.y));
+                if !skip_gaps || outside {
+                    return Some(Impact::NonFinite);
+                }
+
+                in_gap = true;
+                return None;
+            }
+
+            let from = if in_gap {
+                in_gap = false;
+                if !area.inside(&pos) {
+                    return Some(Impact::NonFinite);
+                }
+
+                gaps.push(trajectory.len());
+                pos.clone()
+            } else {
+                // The projectile cannot follow the curve across a jump, so it does not hit
+                // anything on the way
+                let last = trajectory.last().unwrap_or(origin);
+                if jump && last.distance_to(&pos) > max_jump {
+                    return Some(Impact::Discontinuity);
+                }
+
+                last.clone()
+            };
+
+            // Every segment of the curve is checked as a whole, so that nothing is missed between
+            // two samples
+            let seg = Segment::new(from, pos);
+            let wall = area.exit_segment(&seg);
+            let obstacle = arena.obstacle_hit(&seg);
+
+            let (stop, cause) = match (wall, obstacle) {
+                (Some(w), Some(o)) if w < o => (Some(w), Some(Impact::Wall)),
+                (_, Some(o)) => (Some(o), Some(Impact::Obstacle)),
+                (Some(w), None) => (Some(w), Some(Impact::Wall)),
+                (None, None) => (None, None),
+            };
+            let end = stop.map_or_else(|| seg.end().clone(), |t| seg.point_at(t));
+            let travelled = Segment::new(seg.start().clone(), end.clone());
+
+            for (team, player) in arena.players_hit(&travelled, team) {
+                let kill = Kill { team, player };
+                if !killed.contains(&kill) {
+                    killed.push(kill);
+                }
+            }
+
+            trajectory.push(end);
+            cause
+        })
+        .unwrap_or(Impact::Finished);
+
+    let length = trajectory
+        .windows(2)
+        .enumerate()
+        .filter(|(i, _)| !gaps.contains(&(i + 1)))
+        .map(|(_, w)| w[0].distance_to(&w[1]))
+        .sum();
+
+    ShotResult {
+        impact: trajectory.last().unwrap_or(origin).clone(),
+        trajectory,
+        cause,
+        killed,
+        length,
+        gaps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::*;
+    use crate::game::path;
+    use crate::geometry::Circle;
+
+    #[test]
+    fn test_trace_wall() {
+        let arena = Arena::new(10.0, 5.0);
+        let shot = trace(
+            &arena,
+            &Point::new(0.0, 0.0),
+            0,
+            1.0,
+            false,
+            path::explicit(&Point::new(0.0, 0.0), 1.0, |x| x),
+        );
+
+        assert_eq!(shot.cause(), Impact::Wall);
+        assert!(shot.killed().is_empty());
+        assert!(approx_eq!(f64, shot.impact().x, 5.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, shot.impact().y, 5.0, epsilon = 1e-9));
+        assert!(approx_eq!(
+            f64,
+            shot.length(),
+            5.0 * 2.0_f64.sqrt(),
+            epsilon = 0.05
+        ));
+    }
+
+    #[test]
+    fn test_trace_non_finite() {
+        let arena = Arena::new(10.0, 5.0);
+        let shot = trace(
+            &arena,
+            &Point::new(0.0, 0.0),
+            0,
+            1.0,
+            false,
+            path::explicit(&Point::new(0.0, 0.0), 1.0, |x| (1.0 - x).sqrt()),
+        );
+
+        assert_eq!(shot.cause(), Impact::NonFinite);
+        assert!(approx_eq!(f64, shot.impact().x, 1.0, epsilon = 0.01));
+    }
+
+    #[test]
+    fn test_trace_skip_gaps() {
+        // The square root is not defined between -1 and 1
+        let arena = Arena::new(10.0, 5.0);
+        let shot = trace(
+            &arena,
+            &Point::new(-2.0, 0.0),
+            0,
+            1.0,
+            true,
+            path::explicit(&Point::new(-2.0, 0.0), 1.0, |x| (x * x - 1.0).sqrt()),
+        );
+
+        assert_eq!(shot.cause(), Impact::Wall);
+        assert_eq!(shot.gaps().len(), 1);
+        let gap = shot.gaps()[0];
+        assert!(approx_eq!(
+            f64,
+            shot.trajectory()[gap - 1].x,
+            -1.0,
+            epsilon = 0.01
+        ));
+        assert!(approx_eq!(
+            f64,
+            shot.trajectory()[gap].x,
+            1.0,
+            epsilon = 0.01
+        ));
+
+        // The length does not count the gap
+        let truncated = shot.clone().truncate(shot.length() - 0.1);
+        assert_eq!(truncated.gaps(), shot.gaps());
+        assert_eq!(truncated.cause(), Impact::Truncated);
+
+        // A curve that never comes back ends in its gap
+        let shot = trace(
+            &arena,
+            &Point::new(0.0, 0.0),
+            0,
+            1.0,
+            true,
+            path::explicit(&Point::new(0.0, 0.0), 1.0, |x| (1.0 - x).sqrt()),
+        );
+        assert_eq!(shot.cause(), Impact::NonFinite);
+        assert!(shot.gaps().is_empty());
+        assert!(approx_eq!(f64, shot.impact().x, 1.0, epsilon = 0.01));
+    }
+
+    #[test]
+    fn test_trace_thin_obstacle() {
+        // A steep line crosses a small obstacle between two steps of x
+        let mut arena = Arena::new(10.0, 5.0);
+        arena.add_obstacle(Circle::new(Point::new(0.1, 3.0), 0.1));
+
+        let shot = trace(
+            &arena,
+            &Point::new(0.0, 0.0),
+            0,
+            1.0,
+            false,
+            path::explicit(&Point::new(0.0, 0.0), 1.0, |x| 30.0 * x),
+        );
+
+        assert_eq!(shot.cause(), Impact::Obstacle);
+        assert!(Circle::new(Point::new(0.1, 3.0), 0.1 + 1e-9).contains(shot.impact()));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let arena = Arena::new(10.0, 5.0);
+        let shot = trace(
+            &arena,
+            &Point::new(0.0, 0.0),
+            0,
+            1.0,
+            false,
+            path::explicit(&Point::new(0.0, 0.0), 1.0, |x| x),
+        );
+
+        let preview = shot.clone().truncate(2.0);
+        assert_eq!(preview.cause(), Impact::Truncated);
+        assert!(approx_eq!(f64, preview.length(), 2.0));
+        assert!(approx_eq!(
+            f64,
+            preview.impact().x,
+            2.0_f64.sqrt(),
+            epsilon = 1e-9
+        ));
+
+        let full = shot.clone().truncate(100.0);
+        assert_eq!(full.cause(), shot.cause());
+        assert_eq!(full.trajectory().len(), shot.trajectory().len());
+    }
+
+    #[test]
+    fn test_trace_discontinuity() {
+        let arena = Arena::new(10.0, 5.0);
+        let shot = trace(
+            &arena,
+            &Point::new(0.5, 0.0),
+            0,
+            1.0,
+            false,
+            path::explicit(&Point::new(0.5, 0.0), 1.0, |x| 3.0 * x.floor()),
+        );
+
+        assert_eq!(shot.cause(), Impact::Discontinuity);
+        assert!(approx_eq!(f64, shot.impact().x, 1.0, epsilon = 0.02));
+        assert!(approx_eq!(f64, shot.impact().y, 0.0));
+    }
+
+    #[test]
+    fn test_trace_steep() {
+        // The curve leaves the arena between two samples, even after refining the first step
+        let arena = Arena::new(10.0, 5.0);
+        let shot = trace(
+            &arena,
+            &Point::new(0.0, 0.0),
+            0,
+            1.0,
+            false,
+            path::explicit(&Point::new(0.0, 0.0), 1.0, |x| 1e6 * x),
+        );
+
+        assert_eq!(shot.cause(), Impact::Wall);
+        assert!(approx_eq!(f64, shot.impact().y, 5.0, epsilon = 1e-9));
+    }
+}
@@ -25,6 +25,7 @@ impl Team {
         }
     }
 
+    #[allow(clippy::needless_borrow)]
     pub fn add_players<R: Rng + ?Sized>(
         &mut self,
         team_size: usize,
@@ -43,12 +44,41 @@ impl Team {
     }
 
     /// True if the shape collides with any player of the team
+    #[allow(clippy::needless_borrow)]
     pub fn collision_with_player(&self, shape: &Circle) -> bool {
         self.players
             .iter()
             .any(|p| p.shape().collision_circle(&shape))
     }
 
+    pub fn area(&self) -> &Rectangle {
+        &self.area
+    }
+
+    /// Direction along the x axis in which the team shoots: teams on the right half of the arena
+    /// shoot towards negative x
+    pub fn direction(&self) -> f64 {
+        if self.area.pos().x > 0.0 {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+
+    pub fn get_players(&self) -> &Vec<Player> {
+        &self.players
+    }
+
+    /// Marks the player at `idx` as dead, moving the turn to the next alive player if it was the
+    /// current one
+    pub fn kill_player(&mut self, idx: usize) {
+        self.players[idx].kill();
+
+        if idx == self.current_player {
+            self.next_player();
+        }
+    }
+
     pub fn get_current_player(&self) -> Option<&Player> {
         if self.players[self.current_player].alive() {
             Some(&self.players[self.current_player])
@@ -85,6 +115,7 @@ impl Team {
         self.players.iter().any(|p| p.alive())
     }
 
+    #[allow(clippy::needless_borrow)]
     fn find_random_pos<R: Rng + ?Sized>(
         &self,
         player_size: f64,
@@ -30,6 +30,34 @@ impl Circle {
         self.pos.distance_to(&other.pos) <= self.radius + other.radius
     }
 
+    /// True if the point lies inside or on the border of the circle
+    pub fn contains(&self, pos: &Point) -> bool {
+        self.pos.distance_to(pos) <= self.radius
+    }
+
+    /// Fraction of the segment, between 0 and 1, at which it first touches the circle, if it
+    /// does
+    pub fn intersect_segment(&self, seg: &Segment) -> Option<f64> {
+        if self.contains(seg.start()) {
+            return Some(0.0);
+        }
+
+        let d = seg.end() - seg.start();
+        let f = seg.start() - &self.pos;
+
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (f.x * d.x + f.y * d.y);
+        let c = f.x * f.x + f.y * f.y - self.radius * self.radius;
+        let disc = b * b - 4.0 * a * c;
+
+        if a == 0.0 || disc < 0.0 {
+            return None;
+        }
+
+        let t = (-b - disc.sqrt()) / (2.0 * a);
+        (0.0..=1.0).contains(&t).then_some(t)
+    }
+
     pub fn collision_rec(&self, other: &Rectangle) -> bool {
         let x_pos = if self.pos.x <= other.left() {
             other.left()
@@ -108,6 +136,7 @@ impl Rectangle {
     }
 
     /// Partition the rectangle in n rectangles with similar area
+    #[allow(clippy::manual_is_multiple_of)]
     pub fn partition(&self, n: u64) -> Vec<Rectangle> {
         match n {
             1 => vec![self.clone()],
@@ -146,6 +175,7 @@ impl Rectangle {
             && self.bottom() <= other.top()
     }
 
+    #[allow(clippy::needless_borrow)]
     pub fn collision_circle(&self, other: &Circle) -> bool {
         other.collision_rec(&self)
     }
@@ -157,6 +187,34 @@ impl Rectangle {
             && pos.y <= self.top()
     }
 
+    /// Fraction of the segment, between 0 and 1, at which it leaves the rectangle, if it ends
+    /// outside of it
+    pub fn exit_segment(&self, seg: &Segment) -> Option<f64> {
+        let (start, end) = (seg.start(), seg.end());
+        if self.inside(end) {
+            return None;
+        }
+
+        // Fraction at which the segment crosses `bound` along one axis
+        let cross = |from: f64, to: f64, bound: f64| (bound - from) / (to - from);
+
+        let mut t: f64 = 1.0;
+        if end.x > self.right() {
+            t = t.min(cross(start.x, end.x, self.right()));
+        }
+        if end.x < self.left() {
+            t = t.min(cross(start.x, end.x, self.left()));
+        }
+        if end.y > self.top() {
+            t = t.min(cross(start.y, end.y, self.top()));
+        }
+        if end.y < self.bottom() {
+            t = t.min(cross(start.y, end.y, self.bottom()));
+        }
+
+        Some(t.max(0.0))
+    }
+
     pub fn circle_inside(&self, c: &Circle) -> bool {
         let pos = c.pos();
         self.left() + c.radius() <= pos.x
@@ -166,12 +224,58 @@ impl Rectangle {
     }
 }
 
+/// Straight line between two points
+#[derive(Clone, Debug)]
+pub struct Segment {
+    start: Point,
+    end: Point,
+}
+
+impl Segment {
+    pub fn new(start: Point, end: Point) -> Segment {
+        Segment { start, end }
+    }
+
+    pub fn start(&self) -> &Point {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Point {
+        &self.end
+    }
+
+    pub fn length(&self) -> f64 {
+        self.start.distance_to(&self.end)
+    }
+
+    /// Point at fraction `t` of the way from the start to the end
+    pub fn point_at(&self, t: f64) -> Point {
+        Point::new(
+            self.start.x + t * (self.end.x - self.start.x),
+            self.start.y + t * (self.end.y - self.start.y),
+        )
+    }
+
+    /// Shortest distance from the point to any point of the segment
+    pub fn distance_to(&self, pos: &Point) -> f64 {
+        let d = &self.end - &self.start;
+        let len2 = d.x * d.x + d.y * d.y;
+        if len2 == 0.0 {
+            return self.start.distance_to(pos);
+        }
+
+        let t = ((pos.x - self.start.x) * d.x + (pos.y - self.start.y) * d.y) / len2;
+        self.point_at(t.clamp(0.0, 1.0)).distance_to(pos)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use float_cmp::approx_eq;
 
     use super::*;
 
+    #[allow(clippy::bool_assert_comparison)]
     #[test]
     fn test_collision_no() {
         let a = Circle::new(Point::new(0.0, 0.0), 5.0);
@@ -180,6 +284,7 @@ mod tests {
         assert_eq!(a.collision_circle(&b), false);
     }
 
+    #[allow(clippy::bool_assert_comparison)]
     #[test]
     fn test_collision_yes() {
         let a = Circle::new(Point::new(0.0, 0.0), 5.0);
@@ -188,6 +293,7 @@ mod tests {
         assert_eq!(a.collision_circle(&b), true);
     }
 
+    #[allow(clippy::bool_assert_comparison)]
     #[test]
     fn test_collision_rec_yes() {
         let a = Rectangle::new((0.0, 0.0).into(), 10.0, 10.0);
@@ -206,6 +312,7 @@ mod tests {
         }
     }
 
+    #[allow(clippy::bool_assert_comparison)]
     #[test]
     fn test_collision_rec_no() {
         let a = Rectangle::new((0.0, 0.0).into(), 10.0, 10.0);
@@ -224,6 +331,7 @@ mod tests {
         }
     }
 
+    #[allow(clippy::bool_assert_comparison)]
     #[test]
     fn test_collision_rec_circ_yes() {
         let a = Rectangle::new((0.0, 0.0).into(), 10.0, 10.0);
@@ -247,6 +355,7 @@ mod tests {
         }
     }
 
+    #[allow(clippy::bool_assert_comparison)]
     #[test]
     fn test_collision_rec_circ_no() {
         let a = Rectangle::new((0.0, 0.0).into(), 2.0, 2.0);
@@ -315,6 +424,7 @@ mod tests {
         }
     }
 
+    #[allow(clippy::useless_vec)]
     #[test]
     fn test_partition() {
         let a = Rectangle::new((0.0, 0.0).into(), 10.0, 10.0);
@@ -338,4 +448,46 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_segment_circle() {
+        let c = Circle::new(Point::new(0.0, 0.0), 1.0);
+
+        // Crosses the circle between two points that are both outside of it
+        let seg = Segment::new(Point::new(-2.0, 0.5), Point::new(2.0, 0.5));
+        let t = c.intersect_segment(&seg).unwrap();
+        assert!(approx_eq!(f64, seg.point_at(t).x, -(0.75_f64.sqrt())));
+
+        assert_eq!(
+            c.intersect_segment(&Segment::new(Point::new(0.5, 0.0), Point::new(3.0, 0.0))),
+            Some(0.0)
+        );
+        assert_eq!(
+            c.intersect_segment(&Segment::new(Point::new(-2.0, 1.5), Point::new(2.0, 1.5))),
+            None
+        );
+        assert_eq!(
+            c.intersect_segment(&Segment::new(Point::new(2.0, 0.0), Point::new(3.0, 0.0))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_segment_exit() {
+        let a = Rectangle::new((0.0, 0.0).into(), 10.0, 10.0);
+
+        let seg = Segment::new(Point::new(4.0, 0.0), Point::new(6.0, 4.0));
+        assert!(approx_eq!(f64, a.exit_segment(&seg).unwrap(), 0.5));
+
+        let seg = Segment::new(Point::new(0.0, 0.0), Point::new(4.0, 4.0));
+        assert_eq!(a.exit_segment(&seg), None);
+    }
+
+    #[test]
+    fn test_segment_distance() {
+        let seg = Segment::new(Point::new(0.0, 0.0), Point::new(2.0, 0.0));
+
+        assert!(approx_eq!(f64, seg.distance_to(&Point::new(1.0, 1.0)), 1.0));
+        assert!(approx_eq!(f64, seg.distance_to(&Point::new(3.0, 0.0)), 1.0));
+    }
 }
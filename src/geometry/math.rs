@@ -35,6 +35,7 @@ impl Point {
         Point { x, y }
     }
 
+    #[allow(clippy::needless_borrow)]
     pub fn random_default(x_range: &Range<f64>, y_range: &Range<f64>) -> Point {
         Point::random(&x_range, &y_range, &mut rand::thread_rng())
     }
@@ -134,6 +135,7 @@ impl<T: num::Float> Range<T> {
 mod tests {
     use super::*;
 
+    #[allow(clippy::manual_range_contains)]
     #[test]
     fn test_distance() {
         let a = Point::new(0.0, 0.0);
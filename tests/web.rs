@@ -0,0 +1,36 @@
+//! Benchmarks run in the browser, like `benches/eval.rs` does natively.
+
+#![cfg(target_arch = "wasm32")]
+
+use graph_fight::formula::{Function, Program};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const POINTS: usize = 200_000;
+
+#[wasm_bindgen_test]
+fn bench_eval() {
+    let xs: Vec<f64> = (0..POINTS)
+        .map(|i| i as f64 / POINTS as f64 * 20.0 - 10.0)
+        .collect();
+
+    for src in [
+        "2 * x^2 - 3 * x + 1",
+        "sin(3 * x) * exp(-x^2 / 8) + max(0, x - 1)",
+    ] {
+        let f = Function::parse(src, &["x"]).unwrap();
+        let p = Program::compile(&f);
+
+        let start = js_sys::Date::now();
+        let tree: Vec<f64> = xs.iter().map(|&x| f.eval(&[x])).collect();
+        let tree_ms = js_sys::Date::now() - start;
+
+        let start = js_sys::Date::now();
+        let bytecode = p.eval_batch(&xs);
+        let bytecode_ms = js_sys::Date::now() - start;
+
+        assert_eq!(tree.len(), bytecode.len());
+        console_log!("{}: tree {} ms, bytecode {} ms", src, tree_ms, bytecode_ms);
+    }
+}